@@ -0,0 +1,188 @@
+//! Registering flag groups on a `clap::Command` without `derive`.
+//!
+//! Each flag group's arguments are defined once, by its `*Args` type, which also implements
+//! `clap::Args` for `#[clap(flatten)]`.  See [`flag_group_args`] for what an `*Args` provides.
+
+use std::path;
+
+/// Declare `$args` for registering `$group`'s flags and implement `clap::Args` for `$group`
+/// through it.
+///
+/// Each entry is a long name, optionally followed by `=> id` when the argument's id without a
+/// prefix differs from it.  `$args` must provide `augment_args` and `from_arg_matches`.
+macro_rules! flag_group_args {
+    ($args:ident for $group:ident { $($long:literal $(=> $id:literal)?),* $(,)? }) => {
+        #[doc = concat!("Registers [`", stringify!($group), "`]'s flags on a `clap::Command`.")]
+        ///
+        /// `#[clap(flatten)]` registers the same flags.  Here, the ids and long names can also be
+        /// given a prefix with `with_prefix`, e.g. `with_prefix("base-")`, so a command can avoid
+        /// conflicts with its own flags or accept more than one group.  With a prefix, each
+        /// argument's id is its prefixed long name.
+        #[derive(Clone, Debug, PartialEq, Eq)]
+        pub struct $args {
+            names: crate::args::Names,
+        }
+
+        impl $args {
+            const LONGS: &'static [(&'static str, &'static str)] =
+                &[$(($long, crate::args::flag_group_args!(@id $long $(, $id)?))),*];
+
+            /// Flags named like `cargo`'s
+            pub fn new() -> Self {
+                Self::with_prefix("")
+            }
+
+            /// Flags with `prefix` prepended to their ids and long names
+            ///
+            /// Short flags are only registered without a prefix.
+            pub fn with_prefix(prefix: &str) -> Self {
+                Self {
+                    names: crate::args::Names::new(prefix, Self::LONGS),
+                }
+            }
+        }
+
+        impl Default for $args {
+            fn default() -> Self {
+                Self::new()
+            }
+        }
+
+        impl clap::Args for $group {
+            fn augment_args(cmd: clap::Command<'_>) -> clap::Command<'_> {
+                static ARGS: $args = $args {
+                    names: crate::args::Names::unprefixed($args::LONGS),
+                };
+                ARGS.augment_args(cmd)
+            }
+
+            fn augment_args_for_update(cmd: clap::Command<'_>) -> clap::Command<'_> {
+                <Self as clap::Args>::augment_args(cmd)
+            }
+        }
+
+        impl clap::FromArgMatches for $group {
+            fn from_arg_matches(matches: &clap::ArgMatches) -> Result<Self, clap::Error> {
+                Ok($args::new().from_arg_matches(matches))
+            }
+
+            fn update_from_arg_matches(
+                &mut self,
+                matches: &clap::ArgMatches,
+            ) -> Result<(), clap::Error> {
+                *self = $args::new().from_arg_matches(matches);
+                Ok(())
+            }
+        }
+    };
+    (@id $long:literal) => {
+        $long
+    };
+    (@id $long:literal, $id:literal) => {
+        $id
+    };
+}
+pub(crate) use flag_group_args;
+
+/// Ids and long names for the arguments of a flag group, sharing a prefix.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) struct Names {
+    prefix: String,
+    /// Each argument's long name and its id without a prefix
+    longs: &'static [(&'static str, &'static str)],
+    /// Each argument's prefixed long name, also used as its id; empty without a prefix
+    prefixed: Vec<String>,
+}
+
+impl Names {
+    pub(crate) fn new(prefix: &str, longs: &'static [(&'static str, &'static str)]) -> Self {
+        if prefix.is_empty() {
+            return Self::unprefixed(longs);
+        }
+        let prefixed = longs
+            .iter()
+            .map(|(long, _)| format!("{}{}", prefix, long))
+            .collect();
+        Self {
+            prefix: prefix.to_owned(),
+            longs,
+            prefixed,
+        }
+    }
+
+    /// Names as `#[clap(flatten)]` registers them
+    pub(crate) const fn unprefixed(longs: &'static [(&'static str, &'static str)]) -> Self {
+        Self {
+            prefix: String::new(),
+            longs,
+            prefixed: Vec::new(),
+        }
+    }
+
+    fn index(&self, long: &str) -> usize {
+        self.longs
+            .iter()
+            .position(|(l, _)| *l == long)
+            .unwrap_or_else(|| panic!("`{}` is not registered", long))
+    }
+
+    /// The id for `long`
+    pub(crate) fn get(&self, long: &str) -> &str {
+        let index = self.index(long);
+        match self.prefixed.get(index) {
+            Some(prefixed) => prefixed,
+            None => self.longs[index].1,
+        }
+    }
+
+    /// The long name for `long`
+    pub(crate) fn long(&self, long: &str) -> &str {
+        let index = self.index(long);
+        match self.prefixed.get(index) {
+            Some(prefixed) => prefixed,
+            None => self.longs[index].0,
+        }
+    }
+
+    /// An argument for `long`, prefixed
+    pub(crate) fn arg<'help>(&'help self, long: &str) -> clap::Arg<'help> {
+        clap::Arg::new(self.get(long)).long(self.long(long))
+    }
+
+    /// Short flags are only registered without a prefix, to not conflict between groups
+    pub(crate) fn short<'help>(&self, arg: clap::Arg<'help>, short: char) -> clap::Arg<'help> {
+        if self.prefix.is_empty() {
+            arg.short(short)
+        } else {
+            arg
+        }
+    }
+
+    pub(crate) fn flag(&self, matches: &clap::ArgMatches, long: &str) -> bool {
+        matches.is_present(self.get(long))
+    }
+
+    pub(crate) fn value(&self, matches: &clap::ArgMatches, long: &str) -> Option<String> {
+        matches.value_of(self.get(long)).map(ToOwned::to_owned)
+    }
+
+    pub(crate) fn values(&self, matches: &clap::ArgMatches, long: &str) -> Vec<String> {
+        matches
+            .values_of(self.get(long))
+            .map(|values| values.map(ToOwned::to_owned).collect())
+            .unwrap_or_default()
+    }
+
+    /// Requires the argument to `allow_invalid_utf8`
+    pub(crate) fn path(&self, matches: &clap::ArgMatches, long: &str) -> Option<path::PathBuf> {
+        matches.value_of_os(self.get(long)).map(path::PathBuf::from)
+    }
+
+    /// Requires the argument to `allow_invalid_utf8`
+    pub(crate) fn paths(&self, matches: &clap::ArgMatches, long: &str) -> Vec<path::PathBuf> {
+        matches
+            .values_of_os(self.get(long))
+            .map(|values| values.map(path::PathBuf::from).collect())
+            .unwrap_or_default()
+    }
+}
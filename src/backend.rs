@@ -0,0 +1,238 @@
+//! Describing packages independent of where the information came from.
+
+use std::hash::Hash;
+
+/// A kind of dependency between packages.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[non_exhaustive]
+pub enum EdgeKind {
+    /// A `[dependencies]` entry
+    Normal,
+    /// A `[build-dependencies]` entry
+    Build,
+    /// A `[dev-dependencies]` entry
+    Development,
+}
+
+#[cfg(feature = "cargo_metadata")]
+impl From<cargo_metadata::DependencyKind> for EdgeKind {
+    /// Kinds unknown to `cargo_metadata` are treated as normal dependencies
+    fn from(kind: cargo_metadata::DependencyKind) -> Self {
+        match kind {
+            cargo_metadata::DependencyKind::Build => EdgeKind::Build,
+            cargo_metadata::DependencyKind::Development => EdgeKind::Development,
+            _ => EdgeKind::Normal,
+        }
+    }
+}
+
+#[cfg(feature = "cargo_metadata")]
+impl EdgeKind {
+    /// The kinds of a resolved dependency
+    pub(crate) fn of(dep: &cargo_metadata::NodeDep) -> Vec<Self> {
+        if dep.dep_kinds.is_empty() {
+            // `dep_kinds` was added in Rust 1.41
+            return vec![EdgeKind::Normal];
+        }
+        dep.dep_kinds.iter().map(|k| k.kind.into()).collect()
+    }
+}
+
+/// Packages in a workspace and the resolved dependencies between them.
+///
+/// Implement it for any representation, like an existing package graph or a synthetic one, to
+/// use [`Workspace::partition_graph`][crate::Workspace::partition_graph],
+/// [`Features::resolve`][crate::Features::resolve], and [`DependencyGraph`][crate::DependencyGraph]
+/// with them.  [`MetadataGraph`] implements it for `cargo_metadata::Metadata`.
+///
+/// Callers look up packages by id once per package, so lookups should be cheap.
+pub trait WorkspaceGraph {
+    /// Unique identifier for a package
+    type Id: Eq + Hash;
+
+    /// Every known package, including dependencies outside of the workspace
+    fn package_ids(&self) -> Vec<&Self::Id>;
+
+    /// Packages that are members of the workspace
+    fn workspace_members(&self) -> Vec<&Self::Id>;
+
+    /// The package whose manifest was selected, if it is not a virtual workspace
+    fn root(&self) -> Option<&Self::Id>;
+
+    /// The name of the package
+    ///
+    /// May panic if `id` did not come from this graph
+    fn package_name<'g>(&'g self, id: &Self::Id) -> &'g str;
+
+    /// The packages `id` directly depends on and through which kinds of dependencies
+    fn dependencies<'g>(&'g self, id: &Self::Id) -> Vec<(&'g Self::Id, Vec<EdgeKind>)>;
+
+    /// Features declared by `id` and the feature values each one enables
+    fn features<'g>(&'g self, id: &Self::Id) -> Vec<(&'g str, Vec<&'g str>)>;
+}
+
+/// [`WorkspaceGraph`] for `cargo_metadata::Metadata`, indexing its packages once.
+///
+/// Note: Requires the feature `cargo_metadata`.
+#[cfg(feature = "cargo_metadata")]
+#[derive(Clone, Debug)]
+pub struct MetadataGraph<'m> {
+    meta: &'m cargo_metadata::Metadata,
+    packages: std::collections::HashMap<&'m cargo_metadata::PackageId, &'m cargo_metadata::Package>,
+    nodes: std::collections::HashMap<&'m cargo_metadata::PackageId, &'m cargo_metadata::Node>,
+}
+
+#[cfg(feature = "cargo_metadata")]
+impl<'m> MetadataGraph<'m> {
+    /// Index the packages and resolved dependencies of `meta`
+    ///
+    /// Without dependency information (`MetadataCommand::no_deps`), packages have no
+    /// dependencies; check for it with [`Workspace::validate`][crate::Workspace::validate].
+    pub fn new(meta: &'m cargo_metadata::Metadata) -> Self {
+        Self {
+            meta,
+            packages: meta.packages.iter().map(|p| (&p.id, p)).collect(),
+            nodes: meta
+                .resolve
+                .iter()
+                .flat_map(|r| r.nodes.iter())
+                .map(|n| (&n.id, n))
+                .collect(),
+        }
+    }
+
+    /// The indexed `Metadata`
+    pub fn metadata(&self) -> &'m cargo_metadata::Metadata {
+        self.meta
+    }
+
+    /// The package `id`, borrowed from the `Metadata` rather than the index
+    pub fn package(&self, id: &cargo_metadata::PackageId) -> Option<&'m cargo_metadata::Package> {
+        self.packages.get(id).copied()
+    }
+}
+
+#[cfg(feature = "cargo_metadata")]
+impl WorkspaceGraph for MetadataGraph<'_> {
+    type Id = cargo_metadata::PackageId;
+
+    fn package_ids(&self) -> Vec<&Self::Id> {
+        self.meta.packages.iter().map(|p| &p.id).collect()
+    }
+
+    fn workspace_members(&self) -> Vec<&Self::Id> {
+        self.meta.workspace_members.iter().collect()
+    }
+
+    fn root(&self) -> Option<&Self::Id> {
+        self.meta.resolve.as_ref().and_then(|r| r.root.as_ref())
+    }
+
+    fn package_name<'g>(&'g self, id: &Self::Id) -> &'g str {
+        self.packages[id].name.as_str()
+    }
+
+    fn dependencies<'g>(&'g self, id: &Self::Id) -> Vec<(&'g Self::Id, Vec<EdgeKind>)> {
+        self.nodes
+            .get(id)
+            .into_iter()
+            .flat_map(|node| node.deps.iter())
+            .map(|dep| (&dep.pkg, EdgeKind::of(dep)))
+            .collect()
+    }
+
+    fn features<'g>(&'g self, id: &Self::Id) -> Vec<(&'g str, Vec<&'g str>)> {
+        self.packages
+            .get(id)
+            .into_iter()
+            .flat_map(|p| p.features.iter())
+            .map(|(name, values)| (name.as_str(), values.iter().map(|v| v.as_str()).collect()))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod test {
+    use super::*;
+
+    /// A workspace described without `cargo metadata`
+    #[derive(Default, Debug)]
+    pub(crate) struct MemoryGraph {
+        pub(crate) packages: Vec<MemoryPackage>,
+        pub(crate) members: Vec<u32>,
+        pub(crate) root: Option<u32>,
+    }
+
+    #[derive(Default, Debug)]
+    pub(crate) struct MemoryPackage {
+        pub(crate) id: u32,
+        pub(crate) name: &'static str,
+        pub(crate) dependencies: Vec<(u32, EdgeKind)>,
+        pub(crate) features: Vec<(&'static str, Vec<&'static str>)>,
+    }
+
+    impl MemoryGraph {
+        fn package(&self, id: &u32) -> &MemoryPackage {
+            self.packages.iter().find(|p| p.id == *id).unwrap()
+        }
+    }
+
+    impl WorkspaceGraph for MemoryGraph {
+        type Id = u32;
+
+        fn package_ids(&self) -> Vec<&u32> {
+            self.packages.iter().map(|p| &p.id).collect()
+        }
+
+        fn workspace_members(&self) -> Vec<&u32> {
+            self.members.iter().collect()
+        }
+
+        fn root(&self) -> Option<&u32> {
+            self.root.as_ref()
+        }
+
+        fn package_name<'g>(&'g self, id: &u32) -> &'g str {
+            self.package(id).name
+        }
+
+        fn dependencies<'g>(&'g self, id: &u32) -> Vec<(&'g u32, Vec<EdgeKind>)> {
+            self.package(id)
+                .dependencies
+                .iter()
+                .map(|(dep, kind)| (&self.package(dep).id, vec![*kind]))
+                .collect()
+        }
+
+        fn features<'g>(&'g self, id: &u32) -> Vec<(&'g str, Vec<&'g str>)> {
+            self.package(id).features.clone()
+        }
+    }
+
+    #[cfg(feature = "cargo_metadata")]
+    #[test]
+    fn metadata_matches_resolve() {
+        let mut metadata = cargo_metadata::MetadataCommand::new();
+        metadata.manifest_path("tests/fixtures/graph/Cargo.toml");
+        let meta = metadata.exec().unwrap();
+        let graph = MetadataGraph::new(&meta);
+
+        let app = meta.packages.iter().find(|p| p.name == "app").unwrap();
+        assert_eq!(graph.package_name(&app.id), "app");
+        assert_eq!(graph.root(), None);
+        assert_eq!(graph.workspace_members().len(), 5);
+        let mut dependencies: Vec<_> = graph
+            .dependencies(&app.id)
+            .into_iter()
+            .map(|(id, kinds)| (graph.package_name(id), kinds))
+            .collect();
+        dependencies.sort_by_key(|(name, _)| *name);
+        assert_eq!(
+            dependencies,
+            [
+                ("macros", vec![EdgeKind::Normal]),
+                ("testing", vec![EdgeKind::Development]),
+            ]
+        );
+    }
+}
@@ -0,0 +1,305 @@
+//! Finding the `cargo` that invoked a plugin.
+
+use std::ffi;
+use std::path;
+use std::process;
+
+/// The `cargo` to run, preferring the one that invoked this plugin.
+///
+/// In order of precedence:
+/// - A `+toolchain` argument, see [`CargoBinary::from_args`], resolved with `rustup which`
+/// - `$CARGO`, which `cargo` sets when running a plugin, e.g. for `cargo +nightly foo`
+/// - `cargo` from `PATH`, with `RUSTUP_TOOLCHAIN` selecting the toolchain when it is a `rustup`
+///   proxy
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CargoBinary {
+    path: path::PathBuf,
+    toolchain: Option<String>,
+    /// The toolchain was selected by the caller rather than inherited through the environment
+    explicit: bool,
+}
+
+impl CargoBinary {
+    /// `$CARGO`, otherwise `cargo` from `PATH`
+    pub fn from_env() -> Self {
+        let path = std::env::var_os("CARGO")
+            .map(path::PathBuf::from)
+            .unwrap_or_else(|| path::PathBuf::from("cargo"));
+        let toolchain = std::env::var("RUSTUP_TOOLCHAIN").ok();
+        Self {
+            path,
+            toolchain,
+            explicit: false,
+        }
+    }
+
+    /// Like [`CargoBinary::from_env`] unless the first argument after the binary name is a
+    /// `+toolchain`, returning `args` without it.
+    ///
+    /// ```rust,no_run
+    /// let (cargo, args) = clap_cargo::CargoBinary::from_args(std::env::args_os()).unwrap();
+    /// let status = cargo.command().arg("--version").status().unwrap();
+    /// ```
+    pub fn from_args<I, T>(args: I) -> Result<(Self, Vec<ffi::OsString>), crate::Error>
+    where
+        I: IntoIterator<Item = T>,
+        T: Into<ffi::OsString>,
+    {
+        let (toolchain, args) = split_toolchain(args.into_iter().map(Into::into).collect());
+        let cargo = match toolchain {
+            Some(toolchain) => Self::for_toolchain(&toolchain)?,
+            None => Self::from_env(),
+        };
+        Ok((cargo, args))
+    }
+
+    /// The `cargo` of a `rustup` toolchain, e.g. `nightly`
+    pub fn for_toolchain(toolchain: &str) -> Result<Self, crate::Error> {
+        let output = process::Command::new("rustup")
+            .args(["which", "--toolchain", toolchain, "cargo"])
+            .stdin(process::Stdio::null())
+            .output()?;
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            let stderr = stderr.lines().next().unwrap_or_default();
+            return Err(crate::Error::Toolchain {
+                toolchain: toolchain.to_owned(),
+                message: stderr.strip_prefix("error: ").unwrap_or(stderr).to_owned(),
+            });
+        }
+        let path = String::from_utf8_lossy(&output.stdout).trim().to_owned();
+        Ok(Self {
+            path: path.into(),
+            toolchain: Some(toolchain.to_owned()),
+            explicit: true,
+        })
+    }
+
+    /// Path to the `cargo` executable, may be just `cargo` to search `PATH`
+    pub fn path(&self) -> &path::Path {
+        &self.path
+    }
+
+    /// The `rustup` toolchain, if one was selected
+    pub fn toolchain(&self) -> Option<&str> {
+        self.toolchain.as_deref()
+    }
+
+    /// A `process::Command` running this `cargo`
+    ///
+    /// `RUSTUP_TOOLCHAIN` is set for the selected toolchain so `rustc` and other tools run by
+    /// `cargo` come from it as well.
+    pub fn command(&self) -> process::Command {
+        let mut cmd = process::Command::new(&self.path);
+        if let Some(toolchain) = self.toolchain.as_ref() {
+            cmd.env("RUSTUP_TOOLCHAIN", toolchain);
+        }
+        cmd
+    }
+}
+
+#[cfg(feature = "cargo_metadata")]
+impl CargoBinary {
+    /// Create a `cargo_metadata::MetadataCommand` running this `cargo`
+    ///
+    /// `MetadataCommand::exec` cannot set `RUSTUP_TOOLCHAIN`, so the toolchain's `cargo` would run
+    /// the default toolchain's `rustc`; run it with [`CargoBinary::exec_metadata`] instead.
+    ///
+    /// Note: Requires the feature `cargo_metadata`.
+    pub fn metadata(&self) -> cargo_metadata::MetadataCommand {
+        let mut cmd = cargo_metadata::MetadataCommand::new();
+        cmd.cargo_path(&self.path);
+        cmd
+    }
+
+    /// A `process::Command` running `cmd`, with `RUSTUP_TOOLCHAIN` set like [`CargoBinary::command`]
+    ///
+    /// Note: Requires the feature `cargo_metadata`.
+    pub fn metadata_command(&self, cmd: &cargo_metadata::MetadataCommand) -> process::Command {
+        let mut cmd = cmd.cargo_command();
+        if let Some(toolchain) = self.toolchain.as_ref() {
+            cmd.env("RUSTUP_TOOLCHAIN", toolchain);
+        }
+        cmd
+    }
+
+    /// Run `cmd` like `MetadataCommand::exec`, with `RUSTUP_TOOLCHAIN` set for this toolchain
+    ///
+    /// Note: Requires the feature `cargo_metadata`.
+    pub fn exec_metadata(
+        &self,
+        cmd: &cargo_metadata::MetadataCommand,
+    ) -> cargo_metadata::Result<cargo_metadata::Metadata> {
+        cargo_metadata::MetadataCommand::parse(self.metadata_json(cmd)?)
+    }
+
+    /// The JSON printed by `cmd`
+    pub(crate) fn metadata_json(
+        &self,
+        cmd: &cargo_metadata::MetadataCommand,
+    ) -> cargo_metadata::Result<String> {
+        let output = self
+            .metadata_command(cmd)
+            .stdin(process::Stdio::null())
+            .output()?;
+        if !output.status.success() {
+            return Err(cargo_metadata::Error::CargoMetadata {
+                stderr: String::from_utf8(output.stderr)?,
+            });
+        }
+        let stdout = std::str::from_utf8(&output.stdout)?;
+        let json = stdout
+            .lines()
+            .find(|line| line.starts_with('{'))
+            .ok_or(cargo_metadata::Error::NoJson)?;
+        Ok(json.to_owned())
+    }
+}
+
+#[cfg(feature = "escargot")]
+impl CargoBinary {
+    /// Create an `escargot::CargoBuild` for this toolchain
+    ///
+    /// `escargot` always runs `$CARGO` or `cargo` from `PATH`, so a toolchain from
+    /// [`CargoBinary::from_args`] or [`CargoBinary::for_toolchain`] is passed as `+toolchain`.
+    /// This requires that `cargo` to be a `rustup` proxy; a `$CARGO` set by `cargo` itself
+    /// rejects it rather than silently building with another toolchain.
+    ///
+    /// Note: Requires the feature `escargot`.
+    pub fn escargot(&self) -> escargot::CargoBuild {
+        match self.toolchain_arg() {
+            Some(toolchain) => escargot::Cargo::new().arg(toolchain).build(),
+            None => escargot::CargoBuild::new(),
+        }
+    }
+
+    /// `+toolchain` for a toolchain selected by the caller
+    ///
+    /// One inherited through `RUSTUP_TOOLCHAIN` already applies to `$CARGO` or the `rustup`
+    /// proxy.
+    fn toolchain_arg(&self) -> Option<String> {
+        self.toolchain
+            .as_ref()
+            .filter(|_| self.explicit)
+            .map(|toolchain| format!("+{}", toolchain))
+    }
+}
+
+impl Default for CargoBinary {
+    fn default() -> Self {
+        Self::from_env()
+    }
+}
+
+/// Remove a `+toolchain` directly after the binary name
+fn split_toolchain(mut args: Vec<ffi::OsString>) -> (Option<String>, Vec<ffi::OsString>) {
+    let toolchain = args
+        .get(1)
+        .and_then(|arg| arg.to_str())
+        .and_then(|arg| arg.strip_prefix('+'))
+        .map(ToOwned::to_owned);
+    if toolchain.is_some() {
+        args.remove(1);
+    }
+    (toolchain, args)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn toolchain_arg() {
+        let args = |args: &[&str]| -> Vec<ffi::OsString> { args.iter().map(Into::into).collect() };
+
+        assert_eq!(
+            split_toolchain(args(&["cargo-foo", "+nightly", "--workspace"])),
+            (
+                Some("nightly".to_owned()),
+                args(&["cargo-foo", "--workspace"])
+            )
+        );
+        assert_eq!(
+            split_toolchain(args(&["cargo-foo", "foo", "+nightly"])),
+            (None, args(&["cargo-foo", "foo", "+nightly"]))
+        );
+        assert_eq!(
+            split_toolchain(args(&["cargo-foo"])),
+            (None, args(&["cargo-foo"]))
+        );
+    }
+
+    #[test]
+    fn from_args_without_toolchain() {
+        let (cargo, args) = CargoBinary::from_args(["cargo-foo", "foo", "-p", "a"]).unwrap();
+        assert_eq!(cargo, CargoBinary::from_env());
+        assert_eq!(args, ["cargo-foo", "foo", "-p", "a"]);
+    }
+
+    #[test]
+    fn from_env_runs() {
+        let cargo = CargoBinary::from_env();
+        if let Some(path) = std::env::var_os("CARGO") {
+            assert_eq!(cargo.path(), path::Path::new(&path));
+        }
+        assert!(cargo.command().arg("--version").status().unwrap().success());
+    }
+
+    #[test]
+    fn missing_toolchain() {
+        CargoBinary::for_toolchain("no-such-toolchain-clap-cargo").unwrap_err();
+    }
+
+    #[cfg(feature = "escargot")]
+    #[test]
+    fn escargot_toolchain() {
+        // `cargo test` sets `CARGO`, which must not drop an explicit toolchain
+        let cargo = CargoBinary {
+            path: path::PathBuf::from("cargo"),
+            toolchain: Some("nightly".to_owned()),
+            explicit: true,
+        };
+        assert_eq!(cargo.toolchain_arg().as_deref(), Some("+nightly"));
+
+        let cargo = CargoBinary {
+            explicit: false,
+            ..cargo
+        };
+        assert_eq!(cargo.toolchain_arg(), None);
+        assert_eq!(CargoBinary::from_env().toolchain_arg(), None);
+    }
+
+    #[cfg(feature = "cargo_metadata")]
+    #[test]
+    fn metadata() {
+        let cargo = CargoBinary::from_env();
+        let mut cmd = cargo.metadata();
+        cmd.manifest_path("tests/fixtures/simple/Cargo.toml");
+        cargo.exec_metadata(&cmd).unwrap();
+    }
+
+    #[cfg(all(unix, feature = "cargo_metadata"))]
+    #[test]
+    fn metadata_toolchain() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("cargo");
+        std::fs::write(
+            &path,
+            r#"#!/bin/sh
+printf '{"toolchain":"%s"}\n' "$RUSTUP_TOOLCHAIN"
+"#,
+        )
+        .unwrap();
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o755)).unwrap();
+
+        let cargo = CargoBinary {
+            path,
+            toolchain: Some("nightly".to_owned()),
+            explicit: true,
+        };
+        let json = cargo.metadata_json(&cargo.metadata()).unwrap();
+        assert_eq!(json, r#"{"toolchain":"nightly"}"#);
+    }
+}
@@ -0,0 +1,456 @@
+//! Reusing `cargo metadata` output until its inputs change.
+
+use std::fs;
+use std::io;
+use std::path;
+use std::process;
+
+/// Run `cargo metadata`, reusing the output of an earlier run when nothing it depends on changed.
+///
+/// Entries are stored in `clap-cargo/` under the target directory and keyed by the manifest path,
+/// features, and environment.  Like cargo, the target directory is `CARGO_TARGET_DIR`, then
+/// `build.target-dir`, then `target/` in the workspace root, found without running `cargo`.  Each entry records the manifests of the workspace and its path
+/// dependencies, `Cargo.lock`, and the `.cargo/config.toml` files that applied; changing, adding
+/// or removing any of them invalidates the entry.
+///
+/// Note: Requires the feature `cargo_metadata`.
+#[derive(Clone, Debug, Default)]
+pub struct MetadataCache {
+    manifest: crate::Manifest,
+    features: crate::Features,
+    no_deps: bool,
+    dir: Option<path::PathBuf>,
+    cargo: crate::CargoBinary,
+}
+
+impl MetadataCache {
+    /// Cache `cargo metadata` for `manifest`
+    pub fn new(manifest: &crate::Manifest) -> Self {
+        Self {
+            manifest: manifest.clone(),
+            ..Default::default()
+        }
+    }
+
+    /// Forward `features` to `cargo metadata`
+    pub fn features(mut self, features: &crate::Features) -> Self {
+        self.features = features.clone();
+        self
+    }
+
+    /// Skip resolving dependencies, like `MetadataCommand::no_deps`
+    pub fn no_deps(mut self, yes: bool) -> Self {
+        self.no_deps = yes;
+        self
+    }
+
+    /// Run `cargo` instead of [`CargoBinary::from_env`][crate::CargoBinary::from_env]
+    pub fn cargo(mut self, cargo: &crate::CargoBinary) -> Self {
+        self.cargo = cargo.clone();
+        self
+    }
+
+    /// Store entries in `dir` instead of the target directory
+    pub fn dir(mut self, dir: impl Into<path::PathBuf>) -> Self {
+        self.dir = Some(dir.into());
+        self
+    }
+
+    /// Read `cargo metadata` from the cache, running and caching it if missing or out of date.
+    ///
+    /// Failing to read or write the cache is not an error; `cargo metadata` is run instead.  A
+    /// `--metadata-file` is read directly, bypassing the cache.
+    pub fn exec(&self) -> Result<cargo_metadata::Metadata, crate::Error> {
+        if self.manifest.metadata_file.is_some() {
+            return self
+                .manifest
+                .exec_metadata_with(&self.cargo, &self.command());
+        }
+        let entry = self.entry_path()?;
+        if let Some(json) = read_entry(&entry) {
+            if let Ok(meta) = cargo_metadata::MetadataCommand::parse(&json) {
+                return Ok(meta);
+            }
+        }
+
+        let json = self.cargo.metadata_json(&self.command())?;
+        let meta = cargo_metadata::MetadataCommand::parse(&json)?;
+        // The cache is an optimization, running again next time is fine
+        let _ = write_entry(&entry, &json, &inputs(&meta));
+        Ok(meta)
+    }
+
+    fn command(&self) -> cargo_metadata::MetadataCommand {
+        let mut cmd = self.manifest.metadata_with(&self.cargo);
+        self.features.forward_metadata(&mut cmd);
+        if self.no_deps {
+            cmd.no_deps();
+        }
+        cmd
+    }
+
+    fn entry_path(&self) -> Result<path::PathBuf, crate::Error> {
+        let manifest_path = match self.manifest.resolve_manifest_path() {
+            Some(manifest_path) => manifest_path.path().canonicalize()?,
+            None => find_manifest(&std::env::current_dir()?)?,
+        };
+
+        let mut hasher = StableHasher::new();
+        hasher.write_path(&manifest_path);
+        hasher.write_bool(self.features.all_features);
+        hasher.write_bool(self.features.no_default_features);
+        hasher.write_usize(self.features.features.len());
+        for feature in &self.features.features {
+            hasher.write_str(feature);
+        }
+        hasher.write_bool(self.no_deps);
+        hasher.write_path(self.cargo.path());
+        hasher.write_str(self.cargo.toolchain().unwrap_or_default());
+        for (key, value) in std::env::vars_os() {
+            let name = key.to_string_lossy();
+            if KEY_ENV.contains(&name.as_ref())
+                || KEY_ENV_PREFIXES.iter().any(|p| name.starts_with(p))
+            {
+                hasher.write_str(&name);
+                hasher.write_str(&value.to_string_lossy());
+            }
+        }
+
+        let dir = match self.dir.as_ref() {
+            Some(dir) => dir.clone(),
+            None => target_directory(&manifest_path)?.join("clap-cargo"),
+        };
+        Ok(dir.join(format!("metadata-{:016x}.json", hasher.finish())))
+    }
+}
+
+/// The target directory for `manifest_path`, like cargo: `CARGO_TARGET_DIR`, then
+/// `build.target-dir` from the config for the current directory, then `target/` in the workspace
+/// root
+fn target_directory(manifest_path: &path::Path) -> io::Result<path::PathBuf> {
+    let cwd = std::env::current_dir()?;
+    if let Some(dir) = std::env::var_os("CARGO_TARGET_DIR") {
+        return Ok(cwd.join(dir));
+    }
+    if let Some(dir) = crate::CargoConfig::load()?.get_path("build.target-dir")? {
+        return Ok(dir);
+    }
+    Ok(workspace_root(manifest_path).join("target"))
+}
+
+/// The directory of the workspace `manifest_path` belongs to
+///
+/// Like cargo, this is the directory `package.workspace` points to, or else the nearest `[workspace]`
+/// that does not `exclude` the package, or else the package's own.
+fn workspace_root(manifest_path: &path::Path) -> path::PathBuf {
+    let package_root = manifest_path
+        .parent()
+        .unwrap_or_else(|| path::Path::new(""));
+    let manifest = match crate::config::read_table(manifest_path) {
+        Ok(manifest) => manifest,
+        // Like a single-file package
+        Err(_) => return package_root.to_owned(),
+    };
+    if manifest.contains_key("workspace") {
+        return package_root.to_owned();
+    }
+    if let Some(root) =
+        crate::config::lookup(&manifest, "package.workspace").and_then(toml::Value::as_str)
+    {
+        return package_root.join(root);
+    }
+    package_root
+        .ancestors()
+        .skip(1)
+        .find_map(|dir| {
+            let manifest = crate::config::read_table(&dir.join("Cargo.toml")).ok()?;
+            let workspace = manifest.get("workspace")?;
+            let excluded = workspace
+                .get("exclude")
+                .and_then(toml::Value::as_array)
+                .into_iter()
+                .flatten()
+                .filter_map(toml::Value::as_str)
+                .any(|exclude| package_root.starts_with(dir.join(exclude)));
+            (!excluded).then(|| dir.to_owned())
+        })
+        .unwrap_or_else(|| package_root.to_owned())
+}
+
+/// 64-bit FNV-1a, which unlike `DefaultHasher` keeps entries valid across Rust releases
+struct StableHasher(u64);
+
+impl StableHasher {
+    fn new() -> Self {
+        Self(0xcbf2_9ce4_8422_2325)
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.0 ^= u64::from(*byte);
+            self.0 = self.0.wrapping_mul(0x0100_0000_01b3);
+        }
+    }
+
+    /// Written with a terminator so adjacent values cannot run together
+    fn write_str(&mut self, value: &str) {
+        self.write(value.as_bytes());
+        self.write(&[0xff]);
+    }
+
+    fn write_path(&mut self, value: &path::Path) {
+        self.write_str(&value.to_string_lossy());
+    }
+
+    fn write_bool(&mut self, value: bool) {
+        self.write(&[u8::from(value)]);
+    }
+
+    fn write_usize(&mut self, value: usize) {
+        self.write(&(value as u64).to_le_bytes());
+    }
+
+    fn finish(&self) -> u64 {
+        self.0
+    }
+}
+
+/// Environment variables that can change the output of `cargo metadata`
+const KEY_ENV: &[&str] = &["CARGO", "CARGO_HOME", "CARGO_TARGET_DIR", "RUSTC"];
+const KEY_ENV_PREFIXES: &[&str] = &["CARGO_BUILD_", "CARGO_NET_", "CARGO_REGISTRIES_"];
+
+/// Files `meta` was generated from
+fn inputs(meta: &cargo_metadata::Metadata) -> Vec<path::PathBuf> {
+    let root = meta.workspace_root.as_std_path();
+    let mut inputs = vec![root.join("Cargo.toml"), root.join("Cargo.lock")];
+    inputs.extend(
+        meta.packages
+            .iter()
+            // Path dependencies and workspace members
+            .filter(|p| p.source.is_none())
+            .map(|p| p.manifest_path.as_std_path().to_owned()),
+    );
+    for dir in root.ancestors() {
+        inputs.push(dir.join(".cargo/config"));
+        inputs.push(dir.join(".cargo/config.toml"));
+    }
+    if let Ok(cargo_home) = home::cargo_home() {
+        inputs.push(cargo_home.join("config"));
+        inputs.push(cargo_home.join("config.toml"));
+    }
+    inputs.sort();
+    inputs.dedup();
+    inputs
+}
+
+fn read_entry(entry: &path::Path) -> Option<String> {
+    let index = fs::read_to_string(entry.with_extension("inputs")).ok()?;
+    for line in index.lines() {
+        let (hash, path) = line.split_once(' ')?;
+        if hash != file_hash(path::Path::new(path)) {
+            return None;
+        }
+    }
+    fs::read_to_string(entry).ok()
+}
+
+fn write_entry(entry: &path::Path, json: &str, inputs: &[path::PathBuf]) -> io::Result<()> {
+    let mut index = String::new();
+    for input in inputs {
+        let input = input
+            .to_str()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "non-UTF-8 path"))?;
+        index.push_str(&format!(
+            "{} {}\n",
+            file_hash(path::Path::new(input)),
+            input
+        ));
+    }
+    fs::create_dir_all(entry.parent().expect("entries are in a directory"))?;
+    write_atomic(entry, json)?;
+    write_atomic(&entry.with_extension("inputs"), &index)
+}
+
+fn write_atomic(path: &path::Path, content: &str) -> io::Result<()> {
+    let tmp = path.with_extension(format!("tmp{}", process::id()));
+    fs::write(&tmp, content)?;
+    fs::rename(&tmp, path)
+}
+
+/// Hash of the file's content, `-` when it does not exist
+fn file_hash(path: &path::Path) -> String {
+    match fs::read(path) {
+        Ok(content) => {
+            let mut hasher = StableHasher::new();
+            hasher.write(&content);
+            format!("{:016x}", hasher.finish())
+        }
+        Err(_) => "-".to_owned(),
+    }
+}
+
+fn find_manifest(cwd: &path::Path) -> Result<path::PathBuf, crate::Error> {
+    cwd.ancestors()
+        .map(|dir| dir.join("Cargo.toml"))
+        .find(|manifest| manifest.is_file())
+        .ok_or_else(|| crate::Error::ManifestNotFound {
+            cwd: cwd.to_owned(),
+        })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::testing::{PackageSpec, TempWorkspace, WorkspaceSpec};
+
+    fn workspace() -> TempWorkspace {
+        WorkspaceSpec::new()
+            .member(PackageSpec::new("a"))
+            .create()
+            .unwrap()
+    }
+
+    fn cache(dir: &path::Path) -> MetadataCache {
+        let manifest = crate::Manifest {
+            manifest_path: Some(dir.join("Cargo.toml")),
+            ..Default::default()
+        };
+        MetadataCache::new(&manifest).dir(dir.join("cache"))
+    }
+
+    fn entries(dir: &path::Path) -> usize {
+        fs::read_dir(dir.join("cache"))
+            .map(|entries| {
+                entries
+                    .filter(|e| {
+                        let path = e.as_ref().unwrap().path();
+                        path.extension().map(|ext| ext == "json").unwrap_or(false)
+                    })
+                    .count()
+            })
+            .unwrap_or(0)
+    }
+
+    #[test]
+    fn reuses_entry() {
+        let dir = workspace();
+        assert_eq!(entries(dir.root()), 0);
+        let meta = cache(dir.root()).exec().unwrap();
+        assert_eq!(entries(dir.root()), 1);
+
+        // Tamper with the entry so reusing it is observable
+        let entry = cache(dir.root()).entry_path().unwrap();
+        let cached = fs::read_to_string(&entry).unwrap();
+        fs::write(&entry, cached.replace("\"a\"", "\"cached\"")).unwrap();
+        let reused = cache(dir.root()).exec().unwrap();
+        assert_eq!(reused.packages[0].name, "cached");
+        assert_eq!(meta.packages[0].name, "a");
+    }
+
+    #[test]
+    fn invalidated_by_manifest() {
+        let dir = workspace();
+        cache(dir.root()).exec().unwrap();
+        let entry = cache(dir.root()).entry_path().unwrap();
+        let cached = fs::read_to_string(&entry).unwrap();
+        fs::write(&entry, cached.replace("\"a\"", "\"cached\"")).unwrap();
+
+        fs::write(
+            dir.root().join("a/Cargo.toml"),
+            "[package]\nname = \"a\"\nversion = \"0.2.0\"\n",
+        )
+        .unwrap();
+        let meta = cache(dir.root()).exec().unwrap();
+        assert_eq!(meta.packages[0].name, "a");
+        assert_eq!(meta.packages[0].version.to_string(), "0.2.0");
+    }
+
+    #[test]
+    fn invalidated_by_new_config() {
+        let dir = workspace();
+        cache(dir.root()).exec().unwrap();
+        let entry = cache(dir.root()).entry_path().unwrap();
+        let cached = fs::read_to_string(&entry).unwrap();
+        fs::write(&entry, cached.replace("\"a\"", "\"cached\"")).unwrap();
+
+        fs::create_dir_all(dir.root().join(".cargo")).unwrap();
+        fs::write(dir.root().join(".cargo/config.toml"), "").unwrap();
+        let meta = cache(dir.root()).exec().unwrap();
+        assert_eq!(meta.packages[0].name, "a");
+    }
+
+    #[test]
+    fn keyed_by_features() {
+        let dir = workspace();
+        cache(dir.root()).exec().unwrap();
+        let features = crate::Features {
+            no_default_features: true,
+            ..Default::default()
+        };
+        cache(dir.root()).features(&features).exec().unwrap();
+        assert_eq!(entries(dir.root()), 2);
+    }
+
+    #[test]
+    fn stored_in_target_directory() {
+        let dir = workspace();
+        // Not recognizable as a workspace by matching lines
+        fs::write(
+            dir.root().join("Cargo.toml"),
+            "[workspace] # all crates\nmembers = [\"a\"]\n",
+        )
+        .unwrap();
+        let manifest = crate::Manifest::builder()
+            .manifest_path(dir.root().join("a"))
+            .build();
+        let cache = MetadataCache::new(&manifest);
+        cache.exec().unwrap();
+        let entry = cache.entry_path().unwrap();
+        assert!(entry.is_file());
+
+        let target_dir = dir.metadata().unwrap().target_directory;
+        assert_eq!(
+            entry.parent().unwrap().canonicalize().unwrap(),
+            target_dir.join("clap-cargo").canonicalize().unwrap()
+        );
+    }
+
+    #[test]
+    fn finds_workspace_root() {
+        let dir = workspace();
+        let root = dir.root();
+        fs::write(
+            root.join("Cargo.toml"),
+            "[workspace] # all crates\nmembers = [\"a\"]\nexclude = [\"vendor\"]\n",
+        )
+        .unwrap();
+        let package = |dir: &path::Path, extra: &str| {
+            fs::create_dir_all(dir).unwrap();
+            fs::write(
+                dir.join("Cargo.toml"),
+                format!("[package]\nname = \"b\"\nversion = \"0.1.0\"\n{}", extra),
+            )
+            .unwrap();
+            dir.join("Cargo.toml")
+        };
+
+        assert_eq!(workspace_root(&root.join("Cargo.toml")), root);
+        assert_eq!(workspace_root(&root.join("a/Cargo.toml")), root);
+        assert_eq!(
+            workspace_root(&package(&root.join("vendor/b"), "")),
+            root.join("vendor/b")
+        );
+        assert_eq!(
+            workspace_root(&package(&root.join("vendor/c"), "workspace = \"../..\"\n")),
+            root.join("vendor/c/../..")
+        );
+    }
+
+    #[test]
+    fn stable_hash() {
+        let mut hasher = StableHasher::new();
+        hasher.write(b"a");
+        assert_eq!(hasher.finish(), 0xaf63_dc4c_8601_ec8c);
+    }
+}
@@ -0,0 +1,415 @@
+//! Evaluating `cfg()` expressions for platform-specific dependencies.
+
+use std::io;
+use std::process;
+use std::str::FromStr;
+
+/// A single `cfg` value, like `unix` or `target_os = "linux"`.
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Cfg {
+    Name(String),
+    KeyPair(String, String),
+}
+
+impl FromStr for Cfg {
+    type Err = CfgParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parser = Parser::new(s);
+        let cfg = parser.cfg()?;
+        parser.end()?;
+        Ok(cfg)
+    }
+}
+
+impl std::fmt::Display for Cfg {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Name(name) => write!(f, "{}", name),
+            Self::KeyPair(key, value) => write!(f, "{} = \"{}\"", key, value),
+        }
+    }
+}
+
+/// The expression inside of `cfg(...)`.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum CfgExpr {
+    Not(Box<CfgExpr>),
+    All(Vec<CfgExpr>),
+    Any(Vec<CfgExpr>),
+    Value(Cfg),
+}
+
+impl CfgExpr {
+    /// Whether the expression holds for a target with the values `cfg`
+    pub fn matches(&self, cfg: &[Cfg]) -> bool {
+        match self {
+            Self::Not(expr) => !expr.matches(cfg),
+            Self::All(exprs) => exprs.iter().all(|e| e.matches(cfg)),
+            Self::Any(exprs) => exprs.iter().any(|e| e.matches(cfg)),
+            Self::Value(value) => cfg.contains(value),
+        }
+    }
+}
+
+impl FromStr for CfgExpr {
+    type Err = CfgParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parser = Parser::new(s);
+        let expr = parser.expr()?;
+        parser.end()?;
+        Ok(expr)
+    }
+}
+
+/// A `cfg` value or expression is malformed.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CfgParseError {
+    input: String,
+    message: String,
+}
+
+impl std::fmt::Display for CfgParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "failed to parse `{}` as a cfg expression: {}",
+            self.input, self.message
+        )
+    }
+}
+
+impl std::error::Error for CfgParseError {}
+
+/// The `cfg` values of a compilation target.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct TargetCfg {
+    pub triple: String,
+    pub cfg: Vec<Cfg>,
+}
+
+impl TargetCfg {
+    /// Ask `rustc --print cfg` for the values of `triple`, the host when `None`.
+    ///
+    /// The host's triple comes from `rustc -vV`.  Runs `$RUSTC` if set, otherwise `rustc`.
+    pub fn from_rustc(triple: Option<&str>) -> io::Result<Self> {
+        let triple = match triple {
+            Some(triple) => triple.to_owned(),
+            None => rustc(&["-vV"])?
+                .lines()
+                .find_map(|line| line.strip_prefix("host: "))
+                .map(|host| host.trim().to_owned())
+                .ok_or_else(|| {
+                    io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        "`rustc -vV` did not report a host",
+                    )
+                })?,
+        };
+        let cfg = rustc(&["--print", "cfg", "--target", &triple])?
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+            .map(|line| line.parse())
+            .collect::<Result<_, CfgParseError>>()
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+        Ok(Self { triple, cfg })
+    }
+
+    /// Whether a dependency for `platform`, a target triple or `cfg(...)`, applies to this target.
+    pub fn matches(&self, platform: &str) -> Result<bool, CfgParseError> {
+        let platform = platform.trim();
+        match platform
+            .strip_prefix("cfg(")
+            .and_then(|p| p.strip_suffix(')'))
+        {
+            Some(expr) => Ok(expr.parse::<CfgExpr>()?.matches(&self.cfg)),
+            None => Ok(self.triple == platform),
+        }
+    }
+}
+
+fn rustc(args: &[&str]) -> io::Result<String> {
+    let rustc = std::env::var_os("RUSTC").unwrap_or_else(|| "rustc".into());
+    let output = process::Command::new(rustc)
+        .args(args)
+        .stdin(process::Stdio::null())
+        .output()?;
+    if !output.status.success() {
+        return Err(io::Error::new(
+            io::ErrorKind::Other,
+            format!(
+                "`rustc {}` failed: {}",
+                args.join(" "),
+                String::from_utf8_lossy(&output.stderr).trim()
+            ),
+        ));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+impl crate::Compilation {
+    /// The `cfg` values of each `--target`, or of the host without one.
+    ///
+    /// See [`TargetCfg::from_rustc`].
+    pub fn target_cfgs(&self) -> io::Result<Vec<TargetCfg>> {
+        if self.target.is_empty() {
+            return Ok(vec![TargetCfg::from_rustc(None)?]);
+        }
+        self.target
+            .iter()
+            .map(|triple| TargetCfg::from_rustc(Some(triple)))
+            .collect()
+    }
+}
+
+/// Dependencies of `package` that apply to any of `targets`.
+///
+/// Dependencies with a malformed `target` are skipped, as `cargo` would not have loaded them.
+/// See [`Compilation::target_cfgs`][crate::Compilation::target_cfgs] for `targets`.
+///
+/// Note: Requires the feature `cargo_metadata`.
+#[cfg(feature = "cargo_metadata")]
+pub fn filter_dependencies<'m>(
+    package: &'m cargo_metadata::Package,
+    targets: &[TargetCfg],
+) -> Vec<&'m cargo_metadata::Dependency> {
+    package
+        .dependencies
+        .iter()
+        .filter(|dep| match dep.target.as_ref() {
+            Some(platform) => {
+                let platform = platform.to_string();
+                targets
+                    .iter()
+                    .any(|target| target.matches(&platform).unwrap_or(false))
+            }
+            None => true,
+        })
+        .collect()
+}
+
+struct Parser<'s> {
+    input: &'s str,
+    rest: &'s str,
+}
+
+impl<'s> Parser<'s> {
+    fn new(input: &'s str) -> Self {
+        Self { input, rest: input }
+    }
+
+    fn expr(&mut self) -> Result<CfgExpr, CfgParseError> {
+        let checkpoint = self.rest;
+        let ident = self.ident()?;
+        if self.eat('(') {
+            let expr = match ident {
+                "all" => CfgExpr::All(self.list()?),
+                "any" => CfgExpr::Any(self.list()?),
+                "not" => {
+                    let expr = self.expr()?;
+                    self.eat(',');
+                    CfgExpr::Not(Box::new(expr))
+                }
+                _ => return Err(self.error(format!("unknown operator `{}`", ident))),
+            };
+            self.expect(')')?;
+            Ok(expr)
+        } else {
+            self.rest = checkpoint;
+            self.cfg().map(CfgExpr::Value)
+        }
+    }
+
+    fn list(&mut self) -> Result<Vec<CfgExpr>, CfgParseError> {
+        let mut exprs = Vec::new();
+        while !self.peek(')') {
+            exprs.push(self.expr()?);
+            if !self.eat(',') {
+                break;
+            }
+        }
+        Ok(exprs)
+    }
+
+    fn cfg(&mut self) -> Result<Cfg, CfgParseError> {
+        let name = self.ident()?.to_owned();
+        if !self.eat('=') {
+            return Ok(Cfg::Name(name));
+        }
+        self.expect('"')?;
+        let end = self
+            .rest
+            .find('"')
+            .ok_or_else(|| self.error("unterminated string"))?;
+        let value = self.rest[..end].to_owned();
+        self.rest = &self.rest[end + 1..];
+        Ok(Cfg::KeyPair(name, value))
+    }
+
+    fn ident(&mut self) -> Result<&'s str, CfgParseError> {
+        self.skip_whitespace();
+        let end = self
+            .rest
+            .find(|c: char| !(c.is_alphanumeric() || c == '_'))
+            .unwrap_or(self.rest.len());
+        let ident = &self.rest[..end];
+        if ident.is_empty() || ident.starts_with(|c: char| c.is_ascii_digit()) {
+            return Err(self.error("expected an identifier"));
+        }
+        self.rest = &self.rest[end..];
+        Ok(ident)
+    }
+
+    fn peek(&mut self, c: char) -> bool {
+        self.skip_whitespace();
+        self.rest.starts_with(c)
+    }
+
+    fn eat(&mut self, c: char) -> bool {
+        let found = self.peek(c);
+        if found {
+            self.rest = &self.rest[c.len_utf8()..];
+        }
+        found
+    }
+
+    fn expect(&mut self, c: char) -> Result<(), CfgParseError> {
+        if self.eat(c) {
+            Ok(())
+        } else {
+            Err(self.error(format!("expected `{}`", c)))
+        }
+    }
+
+    fn end(&mut self) -> Result<(), CfgParseError> {
+        self.skip_whitespace();
+        if self.rest.is_empty() {
+            Ok(())
+        } else {
+            Err(self.error(format!("unexpected `{}`", self.rest)))
+        }
+    }
+
+    fn skip_whitespace(&mut self) {
+        self.rest = self.rest.trim_start();
+    }
+
+    fn error(&self, message: impl Into<String>) -> CfgParseError {
+        CfgParseError {
+            input: self.input.to_owned(),
+            message: message.into(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn linux() -> Vec<Cfg> {
+        vec![
+            Cfg::Name("unix".to_owned()),
+            Cfg::KeyPair("target_os".to_owned(), "linux".to_owned()),
+            Cfg::KeyPair("target_arch".to_owned(), "x86_64".to_owned()),
+        ]
+    }
+
+    #[test]
+    fn parse() {
+        assert_eq!(
+            "all(unix, not(target_arch = \"x86\"),)".parse::<CfgExpr>(),
+            Ok(CfgExpr::All(vec![
+                CfgExpr::Value(Cfg::Name("unix".to_owned())),
+                CfgExpr::Not(Box::new(CfgExpr::Value(Cfg::KeyPair(
+                    "target_arch".to_owned(),
+                    "x86".to_owned()
+                )))),
+            ]))
+        );
+        assert_eq!("any()".parse::<CfgExpr>(), Ok(CfgExpr::Any(vec![])));
+        assert!("all(unix".parse::<CfgExpr>().is_err());
+        assert!("target_os = linux".parse::<CfgExpr>().is_err());
+        assert!("foo(unix)".parse::<CfgExpr>().is_err());
+        assert!("unix windows".parse::<CfgExpr>().is_err());
+    }
+
+    #[test]
+    fn matches() {
+        let cfg = linux();
+        let matches = |expr: &str| expr.parse::<CfgExpr>().unwrap().matches(&cfg);
+        assert!(matches("unix"));
+        assert!(!matches("windows"));
+        assert!(matches("all(unix, target_arch = \"x86_64\")"));
+        assert!(!matches("all(unix, target_arch = \"aarch64\")"));
+        assert!(matches("any(windows, target_os = \"linux\")"));
+        assert!(matches("not(windows)"));
+        assert!(matches("all()"));
+        assert!(!matches("any()"));
+    }
+
+    #[test]
+    fn target_matches() {
+        let target = TargetCfg {
+            triple: "x86_64-unknown-linux-gnu".to_owned(),
+            cfg: linux(),
+        };
+        assert_eq!(target.matches("cfg(unix)"), Ok(true));
+        assert_eq!(target.matches("cfg(windows)"), Ok(false));
+        assert_eq!(target.matches("x86_64-unknown-linux-gnu"), Ok(true));
+        assert_eq!(target.matches("x86_64-pc-windows-msvc"), Ok(false));
+        assert!(target.matches("cfg(all(unix)").is_err());
+    }
+
+    #[test]
+    fn from_rustc() {
+        let windows = TargetCfg::from_rustc(Some("x86_64-pc-windows-msvc")).unwrap();
+        assert_eq!(windows.matches("cfg(windows)"), Ok(true));
+        assert_eq!(windows.matches("x86_64-pc-windows-msvc"), Ok(true));
+        assert!(TargetCfg::from_rustc(Some("not-a-target")).is_err());
+
+        let host = TargetCfg::from_rustc(None).unwrap();
+        assert_eq!(host.matches(&host.triple), Ok(true));
+        let os = Cfg::KeyPair("target_os".to_owned(), std::env::consts::OS.to_owned());
+        assert!(host.cfg.contains(&os), "{} is missing", os);
+    }
+
+    #[cfg(feature = "cargo_metadata")]
+    #[test]
+    fn platform_dependencies() {
+        let mut metadata = cargo_metadata::MetadataCommand::new();
+        metadata.manifest_path("tests/fixtures/platform/Cargo.toml");
+        let meta = metadata.exec().unwrap();
+        let package = meta.packages.iter().find(|p| p.name == "app").unwrap();
+
+        let names = |targets: &[&str]| -> Vec<String> {
+            let compilation = crate::Compilation {
+                target: targets.iter().map(|t| (*t).to_owned()).collect(),
+                ..Default::default()
+            };
+            let targets = compilation.target_cfgs().unwrap();
+            let mut names: Vec<_> = filter_dependencies(package, &targets)
+                .into_iter()
+                .map(|d| d.name.clone())
+                .collect();
+            names.sort();
+            names
+        };
+        assert_eq!(
+            names(&["x86_64-unknown-linux-gnu"]),
+            ["common", "linux-gnu", "unix"]
+        );
+        assert_eq!(names(&["x86_64-pc-windows-msvc"]), ["common", "windows"]);
+        assert_eq!(
+            names(&["x86_64-unknown-linux-gnu", "x86_64-pc-windows-msvc"]),
+            ["common", "linux-gnu", "unix", "windows"]
+        );
+
+        let host = TargetCfg::from_rustc(None).unwrap();
+        assert_eq!(names(&[]), names(&[host.triple.as_str()]));
+        if host.triple == "x86_64-unknown-linux-gnu" {
+            assert_eq!(names(&[]), ["common", "linux-gnu", "unix"]);
+        }
+    }
+}
@@ -0,0 +1,86 @@
+//! Finding workspace members changed in `git`.
+
+use std::io;
+use std::path;
+use std::process;
+
+/// Workspace members owning a file changed since the merge-base of `rev` and `HEAD`.
+pub(crate) fn changed_packages<'m>(
+    meta: &'m cargo_metadata::Metadata,
+    rev: &str,
+) -> io::Result<Vec<&'m cargo_metadata::PackageId>> {
+    let root = meta.workspace_root.as_std_path();
+    let toplevel = path::PathBuf::from(git(root, &["rev-parse", "--show-toplevel"])?.trim());
+    let base = git(root, &["merge-base", rev, "HEAD"])?;
+    let base = base.trim();
+
+    let mut changed = Vec::new();
+    let diff = git(root, &["diff", "--name-only", "-z", base])?;
+    changed.extend(
+        diff.split('\0')
+            .filter(|p| !p.is_empty())
+            .map(|p| toplevel.join(p)),
+    );
+    let untracked = git(root, &["ls-files", "--others", "--exclude-standard", "-z"])?;
+    // `ls-files` is relative to the current directory
+    changed.extend(
+        untracked
+            .split('\0')
+            .filter(|p| !p.is_empty())
+            .map(|p| root.join(p)),
+    );
+
+    let mut members: Vec<_> = meta
+        .packages
+        .iter()
+        .filter(|p| meta.workspace_members.contains(&p.id))
+        .filter_map(|p| {
+            let dir = p.manifest_path.parent()?.as_std_path();
+            Some((canonicalize(dir), &p.id))
+        })
+        .collect();
+    // Prefer the most nested member when members are nested within each other
+    members.sort_by_key(|(dir, _)| std::cmp::Reverse(dir.components().count()));
+
+    let mut owners = Vec::new();
+    for path in changed {
+        let path = canonicalize_parent(&path);
+        if let Some((_, id)) = members.iter().find(|(dir, _)| path.starts_with(dir)) {
+            if !owners.contains(id) {
+                owners.push(*id);
+            }
+        }
+    }
+    Ok(owners)
+}
+
+fn git(cwd: &path::Path, args: &[&str]) -> io::Result<String> {
+    let output = process::Command::new("git")
+        .args(args)
+        .current_dir(cwd)
+        .stdin(process::Stdio::null())
+        .output()?;
+    if !output.status.success() {
+        return Err(io::Error::new(
+            io::ErrorKind::Other,
+            format!(
+                "`git {}` failed: {}",
+                args.join(" "),
+                String::from_utf8_lossy(&output.stderr).trim()
+            ),
+        ));
+    }
+    String::from_utf8(output.stdout).map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
+}
+
+fn canonicalize(path: &path::Path) -> path::PathBuf {
+    path.canonicalize().unwrap_or_else(|_| path.to_owned())
+}
+
+/// Canonicalize a path that may have been deleted
+fn canonicalize_parent(path: &path::Path) -> path::PathBuf {
+    match (path.parent(), path.file_name()) {
+        (Some(parent), Some(name)) if !path.exists() => canonicalize_parent(parent).join(name),
+        _ => canonicalize(path),
+    }
+}
@@ -0,0 +1,390 @@
+//! Cargo flags for controlling compilation.
+
+use std::path;
+
+#[derive(Default, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "kebab-case", default))]
+#[non_exhaustive]
+pub struct Compilation {
+    /// Number of parallel jobs, defaults to # of CPUs
+    pub jobs: Option<i32>,
+    /// Build for the target triple
+    pub target: Vec<String>,
+    /// Directory for all generated artifacts
+    pub target_dir: Option<path::PathBuf>,
+    /// Do not abort the build as soon as there is an error
+    pub keep_going: bool,
+}
+
+#[cfg(feature = "num_cpus")]
+impl Compilation {
+    /// Number of jobs to run in parallel, like `cargo`.
+    ///
+    /// Negative values are relative to the number of CPUs; the result is always at least 1.
+    /// `--jobs 0` is rejected when parsing, like `cargo`, but `Some(0)` set directly is treated
+    /// as 1.
+    ///
+    /// Note: Requires the feature `num_cpus`, which `cargo_metadata` enables for `Executor`.
+    pub fn jobs(&self) -> usize {
+        let cpus = num_cpus::get();
+        match self.jobs {
+            Some(jobs) if jobs < 0 => cpus.saturating_sub(jobs.unsigned_abs() as usize).max(1),
+            Some(jobs) => (jobs as usize).max(1),
+            None => cpus,
+        }
+    }
+}
+
+#[cfg(feature = "config")]
+impl Compilation {
+    /// Fill in unset flags from `build.jobs`, `build.target`, and `build.target-dir`.
+    ///
+    /// Like `cargo`, `CARGO_TARGET_DIR` and the `CARGO_BUILD_*` environment variables take
+    /// precedence over config files.
+    ///
+    /// Note: Requires the feature `config`.
+    pub fn apply_config(&mut self, config: &crate::CargoConfig) -> std::io::Result<()> {
+        if self.jobs.is_none() {
+            if let Some((jobs, def)) = config.get_integer("build.jobs")? {
+                let jobs = std::convert::TryFrom::try_from(jobs)
+                    .ok()
+                    .filter(|jobs| *jobs != 0)
+                    .ok_or_else(|| {
+                        crate::config::invalid_type("build.jobs", "a non-zero i32", &def)
+                    })?;
+                self.jobs = Some(jobs);
+            }
+        }
+        if self.target.is_empty() {
+            if let Some((target, _)) = config.get_string_list("build.target")? {
+                self.target = target;
+            }
+        }
+        if self.target_dir.is_none() {
+            self.target_dir = match config.env("CARGO_TARGET_DIR") {
+                Some(dir) => Some(config.cwd().join(dir)),
+                None => config.get_path("build.target-dir")?,
+            };
+        }
+        Ok(())
+    }
+}
+
+#[cfg(feature = "escargot")]
+impl Compilation {
+    /// Forward these flags to an `escargot::CargoBuild`.
+    ///
+    /// Note: Requires the features `escargot`.
+    pub fn forward_escargot(&self, mut cmd: escargot::CargoBuild) -> escargot::CargoBuild {
+        if let Some(jobs) = self.jobs {
+            cmd = cmd.arg("--jobs").arg(jobs.to_string());
+        }
+        for target in &self.target {
+            cmd = cmd.target(target);
+        }
+        if let Some(ref target_dir) = self.target_dir {
+            cmd = cmd.target_dir(target_dir);
+        }
+        if self.keep_going {
+            cmd = cmd.arg("--keep-going");
+        }
+        cmd
+    }
+}
+
+/// `--jobs`, rejecting `0` like `cargo`
+fn parse_jobs(jobs: &str) -> Result<i32, String> {
+    match jobs.parse::<i32>() {
+        Ok(0) => Err("jobs may not be 0".to_owned()),
+        Ok(jobs) => Ok(jobs),
+        Err(err) => Err(err.to_string()),
+    }
+}
+
+crate::args::flag_group_args!(CompilationArgs for Compilation {
+    "jobs",
+    "target",
+    "target-dir",
+    "keep-going",
+});
+
+impl CompilationArgs {
+    /// Register the flags on `cmd`.
+    pub fn augment_args<'help>(&'help self, cmd: clap::Command<'help>) -> clap::Command<'help> {
+        let names = &self.names;
+        cmd.arg(
+            names
+                .short(names.arg("jobs"), 'j')
+                .value_name("N")
+                .allow_hyphen_values(true)
+                .validator(parse_jobs)
+                .help("Number of parallel jobs, defaults to # of CPUs"),
+        )
+        .arg(
+            names
+                .arg("target")
+                .value_name("TRIPLE")
+                .multiple_occurrences(true)
+                .help("Build for the target triple"),
+        )
+        .arg(
+            names
+                .arg("target-dir")
+                .value_name("DIRECTORY")
+                .allow_invalid_utf8(true)
+                .help("Directory for all generated artifacts"),
+        )
+        .arg(
+            names
+                .arg("keep-going")
+                .help("Do not abort the build as soon as there is an error"),
+        )
+    }
+
+    /// Read the flags registered by [`CompilationArgs::augment_args`] from `matches`.
+    pub fn from_arg_matches(&self, matches: &clap::ArgMatches) -> Compilation {
+        let names = &self.names;
+        Compilation {
+            jobs: names
+                .value(matches, "jobs")
+                .and_then(|jobs| jobs.parse().ok()),
+            target: names.values(matches, "target"),
+            target_dir: names.path(matches, "target-dir"),
+            keep_going: names.flag(matches, "keep-going"),
+        }
+    }
+}
+
+impl Compilation {
+    /// Build [`Compilation`] flags programmatically.
+    ///
+    /// ```rust
+    /// let compilation = clap_cargo::Compilation::builder().jobs(-1).build();
+    /// assert_eq!(compilation.jobs, Some(-1));
+    /// ```
+    pub fn builder() -> CompilationBuilder {
+        CompilationBuilder::default()
+    }
+}
+
+/// Builder for [`Compilation`], see [`Compilation::builder`].
+#[derive(Default, Clone, Debug, PartialEq, Eq)]
+pub struct CompilationBuilder {
+    inner: Compilation,
+}
+
+impl CompilationBuilder {
+    /// Number of parallel jobs, negative values are relative to the number of CPUs
+    pub fn jobs(mut self, jobs: i32) -> Self {
+        self.inner.jobs = Some(jobs);
+        self
+    }
+
+    /// Build for the target triple, in addition to earlier ones
+    pub fn target(mut self, target: impl Into<String>) -> Self {
+        self.inner.target.push(target.into());
+        self
+    }
+
+    /// Directory for all generated artifacts
+    pub fn target_dir(mut self, target_dir: impl Into<path::PathBuf>) -> Self {
+        self.inner.target_dir = Some(target_dir.into());
+        self
+    }
+
+    /// Do not abort the build as soon as there is an error
+    pub fn keep_going(mut self, yes: bool) -> Self {
+        self.inner.keep_going = yes;
+        self
+    }
+
+    /// Finish building the flags
+    pub fn build(self) -> Compilation {
+        self.inner
+    }
+}
+
+impl From<CompilationBuilder> for Compilation {
+    fn from(builder: CompilationBuilder) -> Self {
+        builder.build()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use clap::StructOpt;
+
+    #[derive(Debug, StructOpt)]
+    struct Args {
+        #[clap(flatten)]
+        compilation: Compilation,
+    }
+
+    #[test]
+    fn verify_app() {
+        use clap::CommandFactory;
+        Args::command().debug_assert()
+    }
+
+    #[test]
+    fn parse() {
+        let args = Args::parse_from([
+            "test",
+            "-j",
+            "-1",
+            "--target",
+            "x86_64-pc-windows-msvc",
+            "--target-dir",
+            "out",
+        ]);
+        assert_eq!(
+            args.compilation,
+            Compilation {
+                jobs: Some(-1),
+                target: vec!["x86_64-pc-windows-msvc".to_owned()],
+                target_dir: Some(path::PathBuf::from("out")),
+                keep_going: false,
+            }
+        );
+    }
+
+    #[cfg(feature = "num_cpus")]
+    #[test]
+    fn jobs() {
+        let compilation = Compilation {
+            jobs: Some(3),
+            ..Default::default()
+        };
+        assert_eq!(compilation.jobs(), 3);
+        let compilation = Compilation {
+            jobs: Some(-10000),
+            ..Default::default()
+        };
+        assert_eq!(compilation.jobs(), 1);
+        let compilation = Compilation {
+            jobs: Some(0),
+            ..Default::default()
+        };
+        assert_eq!(compilation.jobs(), 1);
+        assert!(Compilation::default().jobs() >= 1);
+    }
+
+    #[test]
+    fn zero_jobs_rejected() {
+        assert!(Args::try_parse_from(["test", "-j", "0"]).is_err());
+        let args = CompilationArgs::new();
+        let cmd = args.augment_args(clap::Command::new("test"));
+        assert!(cmd.try_get_matches_from(["test", "--jobs", "0"]).is_err());
+    }
+
+    #[cfg(feature = "config")]
+    #[cfg(test)]
+    mod apply_config {
+        use super::*;
+
+        fn config() -> crate::CargoConfig {
+            crate::CargoConfig::load_from(
+                path::Path::new("tests/fixtures/config/nested"),
+                path::Path::new("tests/fixtures/config/home"),
+            )
+            .unwrap()
+        }
+
+        #[test]
+        fn from_files() {
+            let mut compilation = Compilation::default();
+            compilation.apply_config(&config()).unwrap();
+            assert_eq!(
+                compilation,
+                Compilation {
+                    jobs: Some(2),
+                    target: vec![
+                        "x86_64-unknown-linux-gnu".to_owned(),
+                        "wasm32-unknown-unknown".to_owned()
+                    ],
+                    target_dir: Some(path::PathBuf::from("tests/fixtures/config/custom-target")),
+                    keep_going: false,
+                }
+            );
+        }
+
+        #[test]
+        fn from_env() {
+            let mut config = config();
+            config.set_env(vec![
+                ("CARGO_TARGET_DIR", "env-target"),
+                ("CARGO_BUILD_TARGET", "aarch64-apple-darwin"),
+            ]);
+            let mut compilation = Compilation::default();
+            compilation.apply_config(&config).unwrap();
+            assert_eq!(compilation.target, vec!["aarch64-apple-darwin".to_owned()]);
+            assert_eq!(
+                compilation.target_dir,
+                Some(path::PathBuf::from(
+                    "tests/fixtures/config/nested/env-target"
+                ))
+            );
+        }
+
+        #[test]
+        fn cli_wins() {
+            let mut compilation = Compilation {
+                jobs: Some(5),
+                target: vec!["i686-unknown-linux-gnu".to_owned()],
+                target_dir: Some(path::PathBuf::from("cli-target")),
+                keep_going: true,
+            };
+            let expected = compilation.clone();
+            compilation.apply_config(&config()).unwrap();
+            assert_eq!(compilation, expected);
+        }
+    }
+
+    #[cfg(feature = "escargot")]
+    #[test]
+    fn forward_escargot() {
+        let cmd = escargot::CargoBuild::new().manifest_path("tests/fixtures/simple/Cargo.toml");
+        let compilation = Compilation {
+            jobs: Some(1),
+            ..Default::default()
+        };
+        crate::test_support::build(compilation.forward_escargot(cmd)).unwrap();
+
+        let cmd = escargot::CargoBuild::new().manifest_path("tests/fixtures/simple/Cargo.toml");
+        let compilation = Compilation {
+            target: vec!["not-a-target".to_owned()],
+            ..Default::default()
+        };
+        crate::test_support::build(compilation.forward_escargot(cmd)).unwrap_err();
+    }
+
+    #[test]
+    fn args_with_prefix() {
+        let args = CompilationArgs::with_prefix("host-");
+        let cmd = args.augment_args(clap::Command::new("test"));
+        cmd.clone().debug_assert();
+
+        let matches = cmd
+            .clone()
+            .try_get_matches_from(["test", "--host-jobs", "-1", "--host-target", "wasm32-wasi"])
+            .unwrap();
+        assert_eq!(
+            args.from_arg_matches(&matches),
+            Compilation {
+                jobs: Some(-1),
+                target: vec!["wasm32-wasi".to_owned()],
+                ..Default::default()
+            }
+        );
+        assert!(cmd
+            .clone()
+            .try_get_matches_from(["test", "-j", "1"])
+            .is_err());
+        assert!(cmd
+            .try_get_matches_from(["test", "--host-jobs", "many"])
+            .is_err());
+    }
+}
@@ -0,0 +1,393 @@
+//! Cargo configuration files.
+//!
+//! See <https://doc.rust-lang.org/cargo/reference/config.html>
+
+use std::collections;
+use std::env;
+use std::fs;
+use std::io;
+use std::path;
+
+/// Layered view of cargo's configuration.
+///
+/// Mirrors cargo's discovery:
+/// - `.cargo/config.toml` (or `.cargo/config`) in the current directory and each of its parents
+/// - `$CARGO_HOME/config.toml`
+/// - `$CARGO_HOME/credentials.toml` (or `credentials`), which overrides any config file
+///
+/// Like cargo, the legacy name without `.toml` is used when both exist.
+/// - `CARGO_*` environment variables, which override any file
+///
+/// Note: Requires the feature `config`.
+#[derive(Clone, Debug, Default)]
+pub struct CargoConfig {
+    cwd: path::PathBuf,
+    cargo_home: path::PathBuf,
+    layers: Vec<Layer>,
+    env: collections::BTreeMap<String, String>,
+}
+
+#[derive(Clone, Debug)]
+struct Layer {
+    path: path::PathBuf,
+    table: toml::value::Table,
+}
+
+/// Where a configuration value came from.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Definition {
+    /// Defined in the config file at this path
+    Path(path::PathBuf),
+    /// Defined by this environment variable
+    Environment(String),
+}
+
+impl Definition {
+    /// Resolve `value` the way cargo does for config paths.
+    ///
+    /// Values from a config file are relative to the directory containing `.cargo/`, values from
+    /// the environment are relative to `cwd`.
+    pub fn resolve_path(&self, value: &str, cwd: &path::Path) -> path::PathBuf {
+        let value = path::Path::new(value);
+        if value.is_absolute() {
+            return value.to_owned();
+        }
+        match self {
+            Definition::Path(file) => {
+                let root = file
+                    .parent()
+                    .and_then(|p| p.parent())
+                    .unwrap_or_else(|| path::Path::new(""));
+                root.join(value)
+            }
+            Definition::Environment(_) => cwd.join(value),
+        }
+    }
+}
+
+impl std::fmt::Display for Definition {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Definition::Path(path) => write!(f, "{}", path.display()),
+            Definition::Environment(key) => write!(f, "environment variable `{}`", key),
+        }
+    }
+}
+
+impl CargoConfig {
+    /// Load the configuration cargo would see when run from the current directory.
+    pub fn load() -> io::Result<Self> {
+        let cwd = env::current_dir()?;
+        let cargo_home = home::cargo_home_with_cwd(&cwd)?;
+        let mut config = Self::load_from(&cwd, &cargo_home)?;
+        config.set_env(env::vars());
+        Ok(config)
+    }
+
+    /// Load the config files for `cwd`, ignoring the environment.
+    pub fn load_from(cwd: &path::Path, cargo_home: &path::Path) -> io::Result<Self> {
+        let mut layers = Vec::new();
+
+        // Like cargo, credentials take precedence over all config files
+        if let Some(path) = find_file(cargo_home, "credentials") {
+            let table = read_table(&path)?;
+            layers.push(Layer { path, table });
+        }
+
+        let mut seen = collections::HashSet::new();
+        let candidates = cwd
+            .ancestors()
+            .map(|dir| dir.join(".cargo"))
+            .chain(std::iter::once(cargo_home.to_owned()));
+        for dir in candidates {
+            if let Some(path) = find_file(&dir, "config") {
+                let canonical = path.canonicalize().unwrap_or_else(|_| path.clone());
+                if seen.insert(canonical) {
+                    let table = read_table(&path)?;
+                    layers.push(Layer { path, table });
+                }
+            }
+        }
+
+        Ok(Self {
+            cwd: cwd.to_owned(),
+            cargo_home: cargo_home.to_owned(),
+            layers,
+            env: Default::default(),
+        })
+    }
+
+    /// Replace the environment variables consulted for overrides.
+    ///
+    /// Only `CARGO_*` variables are kept.
+    pub fn set_env<K, V>(&mut self, vars: impl IntoIterator<Item = (K, V)>)
+    where
+        K: Into<String>,
+        V: Into<String>,
+    {
+        self.env = vars
+            .into_iter()
+            .map(|(k, v)| (k.into(), v.into()))
+            .filter(|(k, _)| k.starts_with("CARGO_"))
+            .collect();
+    }
+
+    /// Directory config files were discovered from
+    pub fn cwd(&self) -> &path::Path {
+        &self.cwd
+    }
+
+    /// `$CARGO_HOME` used for discovery
+    pub fn cargo_home(&self) -> &path::Path {
+        &self.cargo_home
+    }
+
+    /// Config files that were loaded, most specific first
+    pub fn paths(&self) -> impl Iterator<Item = &path::Path> {
+        self.layers.iter().map(|l| l.path.as_path())
+    }
+
+    /// Look up an environment variable captured by [`CargoConfig::set_env`]
+    pub fn env(&self, key: &str) -> Option<&str> {
+        self.env.get(key).map(|v| v.as_str())
+    }
+
+    /// Look up a dotted key (e.g. `registries.foo.index`), preferring the environment and then the
+    /// most specific config file.
+    pub fn get(&self, key: &str) -> Option<(toml::Value, Definition)> {
+        let env_key = env_key(key);
+        if let Some(value) = self.env.get(&env_key) {
+            return Some((
+                toml::Value::String(value.clone()),
+                Definition::Environment(env_key),
+            ));
+        }
+        self.layers.iter().find_map(|layer| {
+            lookup(&layer.table, key)
+                .map(|value| (value.clone(), Definition::Path(layer.path.clone())))
+        })
+    }
+
+    /// Look up a string value.
+    pub fn get_string(&self, key: &str) -> io::Result<Option<(String, Definition)>> {
+        match self.get(key) {
+            Some((toml::Value::String(value), def)) => Ok(Some((value, def))),
+            Some((_, def)) => Err(invalid_type(key, "a string", &def)),
+            None => Ok(None),
+        }
+    }
+
+    /// Look up an integer value.
+    pub fn get_integer(&self, key: &str) -> io::Result<Option<(i64, Definition)>> {
+        match self.get(key) {
+            Some((toml::Value::Integer(value), def)) => Ok(Some((value, def))),
+            Some((toml::Value::String(value), def @ Definition::Environment(_))) => value
+                .parse()
+                .map(|value| Some((value, def.clone())))
+                .map_err(|_| invalid_type(key, "an integer", &def)),
+            Some((_, def)) => Err(invalid_type(key, "an integer", &def)),
+            None => Ok(None),
+        }
+    }
+
+    /// Look up a value that may be a string or a list of strings.
+    ///
+    /// Environment variables are treated as a single string.
+    pub fn get_string_list(&self, key: &str) -> io::Result<Option<(Vec<String>, Definition)>> {
+        match self.get(key) {
+            Some((toml::Value::String(value), def)) => Ok(Some((vec![value], def))),
+            Some((toml::Value::Array(values), def)) => values
+                .into_iter()
+                .map(|value| match value {
+                    toml::Value::String(value) => Ok(value),
+                    _ => Err(invalid_type(key, "a list of strings", &def)),
+                })
+                .collect::<io::Result<Vec<_>>>()
+                .map(|values| Some((values, def))),
+            Some((_, def)) => Err(invalid_type(key, "a string or list of strings", &def)),
+            None => Ok(None),
+        }
+    }
+
+    /// Look up a path value, resolved relative to where it was defined.
+    pub fn get_path(&self, key: &str) -> io::Result<Option<path::PathBuf>> {
+        let path = self
+            .get_string(key)?
+            .map(|(value, def)| def.resolve_path(&value, &self.cwd));
+        Ok(path)
+    }
+}
+
+/// `name` or `name.toml` in `dir`, preferring the legacy `name` like cargo
+fn find_file(dir: &path::Path, name: &str) -> Option<path::PathBuf> {
+    let legacy = dir.join(name);
+    if legacy.is_file() {
+        return Some(legacy);
+    }
+    let toml = dir.join(format!("{}.toml", name));
+    toml.is_file().then(|| toml)
+}
+
+pub(crate) fn read_table(path: &path::Path) -> io::Result<toml::value::Table> {
+    let raw = fs::read_to_string(path)?;
+    toml::from_str(&raw).map_err(|err| {
+        io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("could not parse {}: {}", path.display(), err),
+        )
+    })
+}
+
+pub(crate) fn lookup<'t>(table: &'t toml::value::Table, key: &str) -> Option<&'t toml::Value> {
+    let mut parts = key.split('.');
+    let first = parts.next()?;
+    parts.try_fold(table.get(first)?, |value, part| value.get(part))
+}
+
+fn env_key(key: &str) -> String {
+    let mut env_key = String::from("CARGO_");
+    env_key.extend(key.chars().map(|c| match c {
+        '.' | '-' => '_',
+        c => c.to_ascii_uppercase(),
+    }));
+    env_key
+}
+
+pub(crate) fn invalid_type(key: &str, expected: &str, def: &Definition) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidData,
+        format!("`{}` in {} is expected to be {}", key, def, expected),
+    )
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn env_key_mangling() {
+        assert_eq!(env_key("build.target-dir"), "CARGO_BUILD_TARGET_DIR");
+        assert_eq!(
+            env_key("registries.my-reg.index"),
+            "CARGO_REGISTRIES_MY_REG_INDEX"
+        );
+    }
+
+    #[test]
+    fn hierarchy() {
+        let cwd = path::Path::new("tests/fixtures/config/nested");
+        let config =
+            CargoConfig::load_from(cwd, path::Path::new("tests/fixtures/config/home")).unwrap();
+        assert_eq!(config.paths().count(), 4);
+
+        // Closest file wins
+        let (value, def) = config
+            .get_string("registries.shared.index")
+            .unwrap()
+            .unwrap();
+        assert_eq!(value, "https://nested.example.com/index");
+        assert_eq!(
+            def,
+            Definition::Path(path::PathBuf::from(
+                "tests/fixtures/config/nested/.cargo/config.toml"
+            ))
+        );
+
+        // Falls back to parents and then `$CARGO_HOME`
+        let (value, _) = config
+            .get_string("registries.parent.index")
+            .unwrap()
+            .unwrap();
+        assert_eq!(value, "https://parent.example.com/index");
+        let (value, _) = config.get_string("registries.home.index").unwrap().unwrap();
+        assert_eq!(value, "https://home.example.com/index");
+    }
+
+    #[test]
+    fn legacy_names_preferred() {
+        let home = tempfile::tempdir().unwrap();
+        for name in ["config", "config.toml", "credentials", "credentials.toml"] {
+            fs::write(home.path().join(name), "").unwrap();
+        }
+        let config = CargoConfig::load_from(home.path(), home.path()).unwrap();
+        assert_eq!(
+            config.paths().collect::<Vec<_>>(),
+            [home.path().join("credentials"), home.path().join("config")]
+        );
+    }
+
+    #[test]
+    fn env_overrides_files() {
+        let cwd = path::Path::new("tests/fixtures/config/nested");
+        let mut config =
+            CargoConfig::load_from(cwd, path::Path::new("tests/fixtures/config/home")).unwrap();
+        config.set_env(vec![
+            (
+                "CARGO_REGISTRIES_SHARED_INDEX",
+                "https://env.example.com/index",
+            ),
+            ("PATH", "/bin"),
+        ]);
+        let (value, def) = config
+            .get_string("registries.shared.index")
+            .unwrap()
+            .unwrap();
+        assert_eq!(value, "https://env.example.com/index");
+        assert_eq!(
+            def,
+            Definition::Environment("CARGO_REGISTRIES_SHARED_INDEX".to_owned())
+        );
+        assert_eq!(config.env("PATH"), None);
+    }
+
+    #[test]
+    fn invalid_type_errors() {
+        let cwd = path::Path::new("tests/fixtures/config/nested");
+        let config =
+            CargoConfig::load_from(cwd, path::Path::new("tests/fixtures/config/home")).unwrap();
+        assert!(config.get_string("registries.shared").is_err());
+    }
+
+    #[test]
+    fn typed_values() {
+        let cwd = path::Path::new("tests/fixtures/config/nested");
+        let mut config =
+            CargoConfig::load_from(cwd, path::Path::new("tests/fixtures/config/home")).unwrap();
+        let (jobs, _) = config.get_integer("build.jobs").unwrap().unwrap();
+        assert_eq!(jobs, 2);
+        let (targets, _) = config.get_string_list("build.target").unwrap().unwrap();
+        assert_eq!(
+            targets,
+            vec![
+                "x86_64-unknown-linux-gnu".to_owned(),
+                "wasm32-unknown-unknown".to_owned()
+            ]
+        );
+
+        config.set_env(vec![
+            ("CARGO_BUILD_JOBS", "8"),
+            ("CARGO_BUILD_TARGET", "aarch64-apple-darwin"),
+        ]);
+        let (jobs, _) = config.get_integer("build.jobs").unwrap().unwrap();
+        assert_eq!(jobs, 8);
+        let (targets, _) = config.get_string_list("build.target").unwrap().unwrap();
+        assert_eq!(targets, vec!["aarch64-apple-darwin".to_owned()]);
+
+        config.set_env(vec![("CARGO_BUILD_JOBS", "many")]);
+        assert!(config.get_integer("build.jobs").is_err());
+    }
+
+    #[test]
+    fn relative_paths() {
+        let def = Definition::Path(path::PathBuf::from("/repo/.cargo/config.toml"));
+        assert_eq!(
+            def.resolve_path("target", path::Path::new("/repo/sub")),
+            path::PathBuf::from("/repo/target")
+        );
+        let def = Definition::Environment("CARGO_TARGET_DIR".to_owned());
+        assert_eq!(
+            def.resolve_path("target", path::Path::new("/repo/sub")),
+            path::PathBuf::from("/repo/sub/target")
+        );
+    }
+}
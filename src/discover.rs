@@ -0,0 +1,398 @@
+//! Processing several independent workspaces in one invocation.
+
+use std::collections;
+use std::fs;
+use std::io;
+use std::path;
+
+impl crate::Manifest {
+    /// Every workspace selected by `--manifest-path`, `--extra-manifest-path`, and `--discover`,
+    /// see [`Workspaces`].
+    ///
+    /// Without either, this is the workspace of the current directory.
+    ///
+    /// ```rust,no_run
+    /// let manifest = clap_cargo::Manifest::builder().discover(true).build();
+    /// let workspace = clap_cargo::Workspace::default();
+    /// for selection in manifest.workspaces().workspace(&workspace) {
+    ///     let selection = selection.unwrap();
+    ///     for package in selection.included_packages() {
+    ///         println!("{}", package.name);
+    ///     }
+    /// }
+    /// ```
+    ///
+    /// Note: Requires the feature `cargo_metadata`.
+    pub fn workspaces(&self) -> Workspaces {
+        Workspaces::new(self)
+    }
+}
+
+/// Iterates over independent workspaces, yielding each one's metadata and selected packages.
+///
+/// Workspaces are visited in the order of `--manifest-path` and `--extra-manifest-path`, followed
+/// by those found with `--discover` from the shallowest directory down.  A manifest whose package was already
+/// part of a visited workspace, like a member or path dependency, is skipped, so nested
+/// workspaces are only processed once.
+///
+/// Note: Requires the feature `cargo_metadata`.
+#[derive(Clone, Debug)]
+pub struct Workspaces {
+    manifest: crate::Manifest,
+    workspace: crate::Workspace,
+    selection: crate::Selection,
+    features: crate::Features,
+    cargo: crate::CargoBinary,
+    current_dir: Option<path::PathBuf>,
+    pending: Option<collections::VecDeque<crate::ManifestPath>>,
+    visited: collections::HashSet<path::PathBuf>,
+}
+
+impl Workspaces {
+    fn new(manifest: &crate::Manifest) -> Self {
+        Self {
+            manifest: manifest.clone(),
+            workspace: Default::default(),
+            selection: Default::default(),
+            features: Default::default(),
+            cargo: Default::default(),
+            current_dir: None,
+            pending: None,
+            visited: Default::default(),
+        }
+    }
+
+    /// Select packages within each workspace with `workspace`
+    ///
+    /// `--package` is not validated since each spec usually only matches in one of the
+    /// workspaces.
+    pub fn workspace(mut self, workspace: &crate::Workspace) -> Self {
+        self.workspace = workspace.clone();
+        self
+    }
+
+    /// Narrow the selection within each workspace with `selection`, like `--since`
+    pub fn selection(mut self, selection: &crate::Selection) -> Self {
+        self.selection = selection.clone();
+        self
+    }
+
+    /// Forward `features` to `cargo metadata`
+    pub fn features(mut self, features: &crate::Features) -> Self {
+        self.features = features.clone();
+        self
+    }
+
+    /// Run `cargo` instead of [`CargoBinary::from_env`][crate::CargoBinary::from_env]
+    pub fn cargo(mut self, cargo: &crate::CargoBinary) -> Self {
+        self.cargo = cargo.clone();
+        self
+    }
+
+    /// Discover workspaces under `dir` instead of the current directory
+    ///
+    /// Like `cargo`, relative `--manifest-path` and `--extra-manifest-path` values are relative to
+    /// `dir`.
+    pub fn current_dir(mut self, dir: impl Into<path::PathBuf>) -> Self {
+        self.current_dir = Some(dir.into());
+        self
+    }
+
+    fn manifest_paths(&self) -> io::Result<collections::VecDeque<crate::ManifestPath>> {
+        // Absolute, since `cargo` runs in `current_dir` while paths are checked from here
+        let current_dir = match self.current_dir.as_ref() {
+            Some(dir) => Some(std::env::current_dir()?.join(dir)),
+            None => None,
+        };
+        let mut manifest_paths: collections::VecDeque<_> = self
+            .manifest
+            .manifest_path
+            .iter()
+            .chain(self.manifest.extra_manifest_path.iter())
+            .map(|manifest_path| match current_dir.as_ref() {
+                Some(dir) => crate::ManifestPath::resolve(&dir.join(manifest_path)),
+                None => crate::ManifestPath::resolve(manifest_path),
+            })
+            .collect();
+        if self.manifest.discover {
+            let root = match current_dir {
+                Some(dir) => dir,
+                None => std::env::current_dir()?,
+            };
+            let mut found = Vec::new();
+            find_manifests(&root, &mut found)?;
+            found.sort_by(|a, b| {
+                let depth = |p: &path::Path| p.components().count();
+                depth(a).cmp(&depth(b)).then_with(|| a.cmp(b))
+            });
+            manifest_paths.extend(found.into_iter().map(crate::ManifestPath::Toml));
+        }
+        Ok(manifest_paths)
+    }
+
+    fn load(
+        &mut self,
+        manifest_path: Option<&crate::ManifestPath>,
+    ) -> Result<WorkspaceSelection, crate::Error> {
+        let manifest = crate::Manifest {
+            manifest_path: manifest_path.map(|p| p.path().to_owned()),
+            extra_manifest_path: Vec::new(),
+            discover: false,
+            metadata_file: self.manifest.metadata_file.clone(),
+        };
+        let mut cmd = manifest.metadata_with(&self.cargo);
+        if let Some(dir) = self.current_dir.as_ref() {
+            cmd.current_dir(dir);
+        }
+        self.features.forward_metadata(&mut cmd);
+        let metadata = manifest.exec_metadata_with(&self.cargo, &cmd)?;
+
+        let root_manifest = metadata.workspace_root.as_std_path().join("Cargo.toml");
+        self.visited.insert(canonicalize(&root_manifest));
+        for package in metadata.packages.iter().filter(|p| p.source.is_none()) {
+            self.visited
+                .insert(canonicalize(package.manifest_path.as_std_path()));
+        }
+
+        let (included, excluded) = self
+            .selection
+            .partition_packages(&self.workspace, &metadata)?;
+        let included = included.into_iter().map(|p| p.id.clone()).collect();
+        let excluded = excluded.into_iter().map(|p| p.id.clone()).collect();
+        Ok(WorkspaceSelection {
+            manifest_path: manifest_path.cloned(),
+            metadata,
+            included,
+            excluded,
+        })
+    }
+}
+
+impl Iterator for Workspaces {
+    type Item = Result<WorkspaceSelection, crate::Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.pending.is_none() {
+            let manifest_paths = match self.manifest_paths() {
+                Ok(manifest_paths) => manifest_paths,
+                Err(err) => {
+                    self.pending = Some(Default::default());
+                    return Some(Err(err.into()));
+                }
+            };
+            if manifest_paths.is_empty() || self.manifest.metadata_file.is_some() {
+                // A single workspace, from the current directory or `--metadata-file`
+                self.pending = Some(Default::default());
+                return Some(self.load(None));
+            }
+            self.pending = Some(manifest_paths);
+        }
+
+        while let Some(manifest_path) = self.pending.as_mut().and_then(|p| p.pop_front()) {
+            if self.visited.contains(&canonicalize(manifest_path.path())) {
+                continue;
+            }
+            return Some(self.load(Some(&manifest_path)));
+        }
+        None
+    }
+}
+
+/// One workspace's metadata and the packages selected within it, see [`Workspaces`].
+///
+/// Note: Requires the feature `cargo_metadata`.
+#[derive(Clone, Debug)]
+#[non_exhaustive]
+pub struct WorkspaceSelection {
+    /// The manifest the workspace was loaded from, `None` for the current directory or
+    /// `--metadata-file`
+    pub manifest_path: Option<crate::ManifestPath>,
+    /// The workspace's `cargo metadata`
+    pub metadata: cargo_metadata::Metadata,
+    /// Packages selected by [`Selection::partition_packages`][crate::Selection::partition_packages]
+    pub included: Vec<cargo_metadata::PackageId>,
+    /// Packages not selected
+    pub excluded: Vec<cargo_metadata::PackageId>,
+}
+
+impl WorkspaceSelection {
+    /// The selected packages
+    pub fn included_packages(&self) -> Vec<&cargo_metadata::Package> {
+        self.included.iter().map(|id| &self.metadata[id]).collect()
+    }
+
+    /// The packages not selected
+    pub fn excluded_packages(&self) -> Vec<&cargo_metadata::Package> {
+        self.excluded.iter().map(|id| &self.metadata[id]).collect()
+    }
+}
+
+/// `Cargo.toml` files under `dir`, skipping hidden and `target` directories
+fn find_manifests(dir: &path::Path, found: &mut Vec<path::PathBuf>) -> io::Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let name = entry.file_name();
+        let name = name.to_string_lossy();
+        if entry.file_type()?.is_dir() {
+            if !name.starts_with('.') && name != "target" {
+                find_manifests(&entry.path(), found)?;
+            }
+        } else if name == "Cargo.toml" {
+            found.push(entry.path());
+        }
+    }
+    Ok(())
+}
+
+fn canonicalize(path: &path::Path) -> path::PathBuf {
+    path.canonicalize().unwrap_or_else(|_| path.to_owned())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::testing::{PackageSpec, WorkspaceSpec};
+
+    fn write(root: &path::Path, path: &str, content: &str) {
+        let path = root.join(path);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, content).unwrap();
+    }
+
+    /// `tools/` and `services/` are workspaces, `services/nested` is an independent workspace
+    /// within `services/`
+    fn mono_repo() -> tempfile::TempDir {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        WorkspaceSpec::new()
+            .member(PackageSpec::new("lint"))
+            .member(PackageSpec::new("fmt"))
+            .create_at(&root.join("tools"))
+            .unwrap();
+        WorkspaceSpec::new()
+            .member(PackageSpec::new("api"))
+            .create_at(&root.join("services"))
+            .unwrap();
+        WorkspaceSpec::new()
+            .member(PackageSpec::new("nested"))
+            .create_at(&root.join("services/nested"))
+            .unwrap();
+        write(root, "services/target/debug/Cargo.toml", "not a manifest");
+        write(root, ".hidden/Cargo.toml", "not a manifest");
+        dir
+    }
+
+    fn names(workspaces: Workspaces) -> Vec<Vec<String>> {
+        workspaces
+            .map(|selection| {
+                let selection = selection.unwrap();
+                let mut names: Vec<_> = selection
+                    .included_packages()
+                    .into_iter()
+                    .map(|p| p.name.clone())
+                    .collect();
+                names.sort();
+                names
+            })
+            .collect()
+    }
+
+    #[test]
+    fn discover() {
+        let dir = mono_repo();
+        let manifest = crate::Manifest::builder().discover(true).build();
+        let workspace = crate::Workspace {
+            workspace: true,
+            ..Default::default()
+        };
+        let workspaces = manifest
+            .workspaces()
+            .workspace(&workspace)
+            .current_dir(dir.path());
+        assert_eq!(
+            names(workspaces),
+            [vec!["api"], vec!["fmt", "lint"], vec!["nested"]]
+        );
+    }
+
+    #[test]
+    fn manifest_paths() {
+        let dir = mono_repo();
+        let manifest = crate::Manifest::builder()
+            .manifest_path(dir.path().join("tools/lint"))
+            .extra_manifest_path(dir.path().join("services/api/Cargo.toml"))
+            // Already part of the `tools` workspace
+            .extra_manifest_path(dir.path().join("tools/Cargo.toml"))
+            .build();
+        let workspaces = manifest.workspaces();
+        assert_eq!(names(workspaces), [vec!["lint"], vec!["api"]]);
+    }
+
+    #[test]
+    fn relative_manifest_paths() {
+        let dir = mono_repo();
+        let manifest = crate::Manifest::builder()
+            .manifest_path("tools/lint")
+            .extra_manifest_path("services/api/Cargo.toml")
+            // Already part of the `tools` workspace
+            .extra_manifest_path("tools/Cargo.toml")
+            .build();
+        let workspaces = manifest.workspaces().current_dir(dir.path());
+        assert_eq!(names(workspaces), [vec!["lint"], vec!["api"]]);
+    }
+
+    #[test]
+    fn since() {
+        let dir = mono_repo();
+        let git = |args: &[&str]| crate::test_support::git(dir.path(), args);
+        write(dir.path(), ".gitignore", "target\nCargo.lock\n");
+        git(&["init", "-q"]);
+        git(&["add", "."]);
+        git(&["commit", "-q", "-m", "init"]);
+        write(dir.path(), "tools/lint/src/lib.rs", "pub fn changed() {}");
+
+        let manifest = crate::Manifest::builder().discover(true).build();
+        let selection = crate::Selection::builder().since("HEAD").build();
+        let workspaces = manifest
+            .workspaces()
+            .selection(&selection)
+            .current_dir(dir.path());
+        assert_eq!(names(workspaces), [vec![], vec!["lint".to_owned()], vec![]]);
+    }
+
+    #[test]
+    fn no_deps_metadata_file() {
+        let dir = mono_repo();
+        let output = crate::CargoBinary::from_env()
+            .metadata()
+            .manifest_path(dir.path().join("tools/Cargo.toml"))
+            .no_deps()
+            .cargo_command()
+            .output()
+            .unwrap();
+        assert!(output.status.success());
+        write(
+            dir.path(),
+            "metadata.json",
+            &String::from_utf8(output.stdout).unwrap(),
+        );
+
+        let manifest = crate::Manifest::builder()
+            .metadata_file(dir.path().join("metadata.json"))
+            .build();
+        let mut workspaces = manifest.workspaces();
+        let err = workspaces.next().unwrap().unwrap_err();
+        assert!(matches!(err, crate::Error::NoDeps), "{:?}", err);
+        assert!(workspaces.next().is_none());
+    }
+
+    #[test]
+    fn current_dir() {
+        let dir = mono_repo();
+        let workspaces = crate::Manifest::default()
+            .workspaces()
+            .current_dir(dir.path().join("tools/fmt"));
+        assert_eq!(names(workspaces), [vec!["fmt"]]);
+    }
+}
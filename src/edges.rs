@@ -0,0 +1,364 @@
+//! Cargo flags for selecting which dependency edges to follow, like `cargo tree`.
+
+#[cfg(feature = "cargo_metadata")]
+use std::collections;
+
+#[derive(Default, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "kebab-case", default))]
+#[non_exhaustive]
+pub struct Edges {
+    /// The kinds of dependencies to follow (normal, build, dev, features, all, no-normal, no-build, no-dev, no-proc-macro)
+    pub edges: Vec<String>,
+}
+
+impl Edges {
+    /// Build [`Edges`] flags programmatically.
+    ///
+    /// ```rust
+    /// let edges = clap_cargo::Edges::builder().edge("no-dev").build();
+    /// assert_eq!(edges.edges, ["no-dev"]);
+    /// ```
+    pub fn builder() -> EdgesBuilder {
+        EdgesBuilder::default()
+    }
+}
+
+/// Builder for [`Edges`], see [`Edges::builder`].
+#[derive(Default, Clone, Debug, PartialEq, Eq)]
+pub struct EdgesBuilder {
+    inner: Edges,
+}
+
+impl EdgesBuilder {
+    /// Follow edges of `kind`, in addition to earlier ones
+    pub fn edge(mut self, kind: impl Into<String>) -> Self {
+        self.inner.edges.push(kind.into());
+        self
+    }
+
+    /// Finish building the flags
+    pub fn build(self) -> Edges {
+        self.inner
+    }
+}
+
+impl From<EdgesBuilder> for Edges {
+    fn from(builder: EdgesBuilder) -> Self {
+        builder.build()
+    }
+}
+
+crate::args::flag_group_args!(EdgesArgs for Edges { "edges" });
+
+impl EdgesArgs {
+    /// Register the flags on `cmd`.
+    pub fn augment_args<'help>(&'help self, cmd: clap::Command<'help>) -> clap::Command<'help> {
+        let names = &self.names;
+        cmd.arg(
+            names
+                .short(names.arg("edges"), 'e')
+                .value_name("KINDS")
+                .multiple_occurrences(true)
+                .use_value_delimiter(true)
+                .value_delimiter(',')
+                .help("The kinds of dependencies to follow (normal, build, dev, features, all, no-normal, no-build, no-dev, no-proc-macro)"),
+        )
+    }
+
+    /// Read the flags registered by [`EdgesArgs::augment_args`] from `matches`.
+    pub fn from_arg_matches(&self, matches: &clap::ArgMatches) -> Edges {
+        let names = &self.names;
+        Edges {
+            edges: names.values(matches, "edges"),
+        }
+    }
+}
+
+/// Edges selected by [`Edges::kinds`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct EdgeKinds {
+    /// Dependency kinds to follow
+    pub kinds: Vec<crate::EdgeKind>,
+    /// Whether feature edges were requested
+    ///
+    /// [`DependencyGraph`][crate::DependencyGraph] has no feature nodes, so this does not affect
+    /// [`EdgeKinds::dependencies`].
+    pub features: bool,
+    /// Skip proc-macro dependencies
+    pub no_proc_macro: bool,
+}
+
+/// `--edges` has an unknown or contradicting kind.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct EdgeKindError {
+    pub kind: String,
+    message: String,
+}
+
+impl std::fmt::Display for EdgeKindError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.message.fmt(f)
+    }
+}
+
+impl std::error::Error for EdgeKindError {}
+
+impl Edges {
+    /// Interpret `--edges` like `cargo tree`.
+    ///
+    /// - Without `--edges`, normal, build, and dev dependencies are followed
+    /// - `all` follows every kind of dependency and features
+    /// - `features` alone also follows every kind of dependency
+    /// - `no-normal`, `no-build`, and `no-dev` remove a kind from the defaults and cannot be mixed
+    ///   with `normal`, `build`, `dev`, or `all`
+    pub fn kinds(&self) -> Result<EdgeKinds, EdgeKindError> {
+        use crate::EdgeKind;
+
+        const DEFAULTS: [EdgeKind; 3] = [EdgeKind::Normal, EdgeKind::Build, EdgeKind::Development];
+
+        let mut no_proc_macro = false;
+        let kinds: Vec<_> = self
+            .edges
+            .iter()
+            .map(|kind| kind.trim())
+            .filter(|kind| {
+                let is_no_proc_macro = *kind == "no-proc-macro";
+                no_proc_macro |= is_no_proc_macro;
+                !is_no_proc_macro
+            })
+            .collect();
+
+        let mut result = EdgeKinds {
+            kinds: Vec::new(),
+            features: false,
+            no_proc_macro,
+        };
+        if kinds.is_empty() {
+            result.kinds.extend(DEFAULTS.iter().copied());
+            return Ok(result);
+        }
+
+        if kinds.iter().any(|kind| kind.starts_with("no-")) {
+            result.kinds.extend(DEFAULTS.iter().copied());
+            for kind in &kinds {
+                let removed = match *kind {
+                    "no-normal" => EdgeKind::Normal,
+                    "no-build" => EdgeKind::Build,
+                    "no-dev" => EdgeKind::Development,
+                    "features" => {
+                        result.features = true;
+                        continue;
+                    }
+                    "normal" | "build" | "dev" | "all" => {
+                        return Err(EdgeKindError {
+                            kind: (*kind).to_owned(),
+                            message: format!(
+                                "`{}` dependency kind cannot be mixed with \"no-normal\", \"no-build\", or \"no-dev\" dependency kinds",
+                                kind
+                            ),
+                        })
+                    }
+                    _ => return Err(unknown(kind)),
+                };
+                result.kinds.retain(|k| *k != removed);
+            }
+            return Ok(result);
+        }
+
+        for kind in &kinds {
+            let added = match *kind {
+                "normal" => EdgeKind::Normal,
+                "build" => EdgeKind::Build,
+                "dev" => EdgeKind::Development,
+                "features" => {
+                    result.features = true;
+                    continue;
+                }
+                "all" => {
+                    result.features = true;
+                    for kind in DEFAULTS.iter() {
+                        if !result.kinds.contains(kind) {
+                            result.kinds.push(*kind);
+                        }
+                    }
+                    continue;
+                }
+                _ => return Err(unknown(kind)),
+            };
+            if !result.kinds.contains(&added) {
+                result.kinds.push(added);
+            }
+        }
+        if kinds == ["features"] {
+            result.kinds.extend(DEFAULTS.iter().copied());
+        }
+        Ok(result)
+    }
+}
+
+fn unknown(kind: &str) -> EdgeKindError {
+    EdgeKindError {
+        kind: kind.to_owned(),
+        message: format!(
+            "unknown edge kind `{}`, valid values are \"normal\", \"build\", \"dev\", \"no-normal\", \"no-build\", \"no-dev\", \"no-proc-macro\", \"features\", or \"all\"",
+            kind
+        ),
+    }
+}
+
+#[cfg(feature = "cargo_metadata")]
+impl EdgeKinds {
+    /// [`WalkOptions`][crate::WalkOptions] following these kinds of dependencies.
+    ///
+    /// This cannot express `no-proc-macro`, see [`EdgeKinds::dependencies`].
+    pub fn walk_options(&self) -> crate::WalkOptions {
+        crate::WalkOptions {
+            kinds: self.kinds.clone(),
+            ..Default::default()
+        }
+    }
+
+    /// `seeds` and the packages they transitively depend on through the selected edges.
+    ///
+    /// Without dependency information (`MetadataCommand::no_deps`), this is only `seeds`.
+    pub fn dependencies<'m>(
+        &self,
+        meta: &'m cargo_metadata::Metadata,
+        seeds: impl IntoIterator<Item = &'m cargo_metadata::PackageId>,
+    ) -> collections::HashSet<&'m cargo_metadata::PackageId> {
+        let graph = crate::DependencyGraph::new(meta);
+        let proc_macros: collections::HashSet<_> = if self.no_proc_macro {
+            meta.packages
+                .iter()
+                .filter(|p| {
+                    p.targets
+                        .iter()
+                        .any(|t| t.kind.iter().any(|k| k == "proc-macro"))
+                })
+                .map(|p| &p.id)
+                .collect()
+        } else {
+            collections::HashSet::new()
+        };
+
+        let mut visited = collections::HashSet::new();
+        let mut pending: Vec<_> = seeds.into_iter().collect();
+        while let Some(id) = pending.pop() {
+            if !visited.insert(id) {
+                continue;
+            }
+            pending.extend(
+                graph
+                    .direct_dependencies(id, &self.kinds)
+                    .filter(|dep| !proc_macros.contains(dep)),
+            );
+        }
+        visited
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use clap::StructOpt;
+
+    #[derive(Debug, StructOpt)]
+    struct Args {
+        #[clap(flatten)]
+        edges: Edges,
+    }
+
+    #[test]
+    fn verify_app() {
+        use clap::CommandFactory;
+        Args::command().debug_assert()
+    }
+
+    #[test]
+    fn parse() {
+        let args = Args::parse_from(["test", "-e", "normal,build", "--edges", "no-proc-macro"]);
+        assert_eq!(args.edges.edges, ["normal", "build", "no-proc-macro"]);
+    }
+
+    #[test]
+    fn args_match_derive() {
+        let args = EdgesArgs::new();
+        let cmd = args.augment_args(clap::Command::new("test"));
+        cmd.clone().debug_assert();
+
+        let argv = ["test", "-e", "normal,build", "--edges", "no-proc-macro"];
+        let matches = cmd.try_get_matches_from(argv).unwrap();
+        assert_eq!(
+            args.from_arg_matches(&matches),
+            Args::parse_from(argv).edges
+        );
+    }
+
+    #[test]
+    fn kinds() {
+        use crate::EdgeKind::*;
+
+        let kinds = |edges: &[&str]| {
+            let edges = Edges {
+                edges: edges.iter().map(|e| (*e).to_owned()).collect(),
+            };
+            edges
+                .kinds()
+                .map(|k| (k.kinds, k.features, k.no_proc_macro))
+        };
+        assert_eq!(
+            kinds(&[]),
+            Ok((vec![Normal, Build, Development], false, false))
+        );
+        assert_eq!(kinds(&["normal"]), Ok((vec![Normal], false, false)));
+        assert_eq!(
+            kinds(&["no-dev", "no-proc-macro"]),
+            Ok((vec![Normal, Build], false, true))
+        );
+        assert_eq!(
+            kinds(&["features"]),
+            Ok((vec![Normal, Build, Development], true, false))
+        );
+        assert_eq!(
+            kinds(&["features", "normal"]),
+            Ok((vec![Normal], true, false))
+        );
+        assert_eq!(
+            kinds(&["all"]),
+            Ok((vec![Normal, Build, Development], true, false))
+        );
+        assert_eq!(kinds(&["no-dev", "dev"]).unwrap_err().kind, "dev");
+        assert_eq!(kinds(&["bogus"]).unwrap_err().kind, "bogus");
+    }
+
+    #[cfg(feature = "cargo_metadata")]
+    #[test]
+    fn dependencies() {
+        let mut metadata = cargo_metadata::MetadataCommand::new();
+        metadata.manifest_path("tests/fixtures/graph/Cargo.toml");
+        let meta = metadata.exec().unwrap();
+        let id = |name: &str| &meta.packages.iter().find(|p| p.name == name).unwrap().id;
+        let names = |edges: &[&str]| {
+            let edges = Edges {
+                edges: edges.iter().map(|e| (*e).to_owned()).collect(),
+            };
+            let ids = edges.kinds().unwrap().dependencies(&meta, vec![id("app")]);
+            let mut names: Vec<_> = meta
+                .packages
+                .iter()
+                .filter(|p| ids.contains(&p.id))
+                .map(|p| p.name.as_str())
+                .collect();
+            names.sort_unstable();
+            names
+        };
+        assert_eq!(names(&[]), ["app", "base", "macros", "testing"]);
+        assert_eq!(names(&["no-dev"]), ["app", "base", "macros"]);
+        assert_eq!(names(&["dev"]), ["app", "testing"]);
+        assert_eq!(names(&["no-proc-macro"]), ["app", "testing"]);
+    }
+}
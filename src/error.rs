@@ -0,0 +1,357 @@
+//! Failures reported like `cargo` reports them.
+
+use std::fmt;
+use std::io;
+use std::path;
+
+/// Exit code `cargo` uses for failures
+pub const FAILURE_EXIT_CODE: i32 = 101;
+
+/// Any failure from this crate.
+///
+/// `Display` renders the message followed by `note:` and `help:` lines, matching `cargo`, so
+/// printing it with [`Shell::error`][crate::Shell::error] looks like:
+///
+/// ```text
+/// error: package ID specification `serd` did not match any packages
+/// help: a package with a similar name exists: `serde`
+/// ```
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum Error {
+    /// No `Cargo.toml` in the directory or its ancestors
+    ManifestNotFound {
+        /// Where the search started
+        cwd: path::PathBuf,
+    },
+    /// Running or parsing `cargo metadata` failed
+    ///
+    /// Note: Requires the feature `cargo_metadata`.
+    #[cfg(feature = "cargo_metadata")]
+    Metadata(cargo_metadata::Error),
+    /// A `--metadata-file` is not `cargo metadata` output in a supported format version
+    MetadataFormat {
+        /// The file that was read, `-` for stdin
+        path: path::PathBuf,
+        /// The format version it declares, `None` when missing
+        found: Option<u64>,
+        /// The format version that can be read
+        supported: u64,
+    },
+    /// `cargo metadata` was run with `--no-deps` but dependencies were needed
+    NoDeps,
+    /// `--package` matched no workspace member
+    PackageNotFound {
+        /// The unmatched spec
+        spec: String,
+        /// A workspace member with a similar name
+        similar: Option<String>,
+    },
+    /// `--features` named a feature none of the selected packages have
+    FeatureNotFound {
+        /// The unknown feature
+        feature: String,
+        /// The packages that were checked
+        packages: Vec<String>,
+        /// A feature with a similar name
+        similar: Option<String>,
+    },
+    /// Workspace members depend on each other in a cycle
+    ///
+    /// Note: Requires the feature `cargo_metadata`.
+    #[cfg(feature = "cargo_metadata")]
+    Cycle(crate::CycleError),
+    /// A malformed `cfg()` expression
+    Cfg(crate::CfgParseError),
+    /// An unknown or contradicting `--edges` kind
+    EdgeKind(crate::EdgeKindError),
+    /// A `+toolchain` is not installed
+    Toolchain {
+        /// The requested toolchain
+        toolchain: String,
+        /// Why `rustup` could not find it
+        message: String,
+    },
+    /// Reading files or running a process failed
+    Io(io::Error),
+}
+
+impl Error {
+    /// The process exit code `cargo` uses for this error
+    pub fn exit_code(&self) -> i32 {
+        FAILURE_EXIT_CODE
+    }
+
+    /// Context printed as `note:`
+    pub fn note(&self) -> Option<String> {
+        match self {
+            #[cfg(feature = "cargo_metadata")]
+            Error::Metadata(cargo_metadata::Error::CargoMetadata { stderr }) => {
+                let stderr = stderr.trim();
+                let stderr = stderr.strip_prefix("error: ").unwrap_or(stderr);
+                Some(stderr.to_owned())
+            }
+            Error::MetadataFormat { supported, .. } => {
+                Some(format!("expected format version {}", supported))
+            }
+            Error::Toolchain { message, .. } if !message.is_empty() => Some(message.clone()),
+            Error::FeatureNotFound { packages, .. } if 1 < packages.len() => {
+                Some(format!("selected packages: {}", packages.join(", ")))
+            }
+            _ => None,
+        }
+    }
+
+    /// A suggestion printed as `help:`
+    pub fn help(&self) -> Option<String> {
+        match self {
+            Error::ManifestNotFound { .. } => {
+                Some("pass `--manifest-path` to select a `Cargo.toml`".to_owned())
+            }
+            Error::MetadataFormat { supported, .. } => Some(format!(
+                "generate it with `cargo metadata --format-version {}`",
+                supported
+            )),
+            Error::NoDeps => Some("run `cargo metadata` without `--no-deps`".to_owned()),
+            Error::Toolchain { toolchain, .. } => Some(format!(
+                "install it with `rustup toolchain install {}`",
+                toolchain
+            )),
+            Error::PackageNotFound {
+                similar: Some(similar),
+                ..
+            } => Some(format!(
+                "a package with a similar name exists: `{}`",
+                similar
+            )),
+            Error::FeatureNotFound {
+                similar: Some(similar),
+                ..
+            } => Some(format!(
+                "a feature with a similar name exists: `{}`",
+                similar
+            )),
+            _ => None,
+        }
+    }
+
+    fn message(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::ManifestNotFound { cwd } => write!(
+                f,
+                "could not find `Cargo.toml` in `{}` or any parent directory",
+                cwd.display()
+            ),
+            #[cfg(feature = "cargo_metadata")]
+            Error::Metadata(cargo_metadata::Error::CargoMetadata { .. }) => {
+                fmt::Display::fmt("`cargo metadata` exited with an error", f)
+            }
+            #[cfg(feature = "cargo_metadata")]
+            Error::Metadata(err) => write!(f, "failed to read `cargo metadata`: {}", err),
+            Error::MetadataFormat {
+                path,
+                found: Some(found),
+                ..
+            } => write!(
+                f,
+                "unsupported `cargo metadata` format version {} in `{}`",
+                found,
+                path.display()
+            ),
+            Error::MetadataFormat {
+                path, found: None, ..
+            } => write!(
+                f,
+                "`{}` is not `cargo metadata` output, it has no format version",
+                path.display()
+            ),
+            Error::NoDeps => {
+                fmt::Display::fmt("dependency information is missing from `cargo metadata`", f)
+            }
+            Error::PackageNotFound { spec, .. } => write!(
+                f,
+                "package ID specification `{}` did not match any packages",
+                spec
+            ),
+            Error::FeatureNotFound {
+                feature, packages, ..
+            } => match packages.as_slice() {
+                [package] => write!(
+                    f,
+                    "package `{}` does not have the feature `{}`",
+                    package, feature
+                ),
+                _ => write!(
+                    f,
+                    "none of the selected packages contains the feature `{}`",
+                    feature
+                ),
+            },
+            #[cfg(feature = "cargo_metadata")]
+            Error::Cycle(err) => fmt::Display::fmt(err, f),
+            Error::Cfg(err) => fmt::Display::fmt(err, f),
+            Error::Toolchain { toolchain, .. } => {
+                write!(f, "could not find `cargo` for toolchain `{}`", toolchain)
+            }
+            Error::EdgeKind(err) => fmt::Display::fmt(err, f),
+            Error::Io(err) => fmt::Display::fmt(err, f),
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.message(f)?;
+        if let Some(note) = self.note() {
+            write!(f, "\nnote: {}", note)?;
+        }
+        if let Some(help) = self.help() {
+            write!(f, "\nhelp: {}", help)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            #[cfg(feature = "cargo_metadata")]
+            Error::Metadata(err) => Some(err),
+            #[cfg(feature = "cargo_metadata")]
+            Error::Cycle(err) => Some(err),
+            Error::Cfg(err) => Some(err),
+            Error::EdgeKind(err) => Some(err),
+            Error::Io(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(feature = "cargo_metadata")]
+impl From<cargo_metadata::Error> for Error {
+    fn from(err: cargo_metadata::Error) -> Self {
+        match err {
+            cargo_metadata::Error::Io(err) => Error::Io(err),
+            err => Error::Metadata(err),
+        }
+    }
+}
+
+#[cfg(feature = "cargo_metadata")]
+impl From<crate::CycleError> for Error {
+    fn from(err: crate::CycleError) -> Self {
+        Error::Cycle(err)
+    }
+}
+
+impl From<crate::CfgParseError> for Error {
+    fn from(err: crate::CfgParseError) -> Self {
+        Error::Cfg(err)
+    }
+}
+
+impl From<crate::EdgeKindError> for Error {
+    fn from(err: crate::EdgeKindError) -> Self {
+        Error::EdgeKind(err)
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(err: io::Error) -> Self {
+        Error::Io(err)
+    }
+}
+
+/// The candidate closest to `name`, if close enough to be a likely typo
+pub(crate) fn similar_name<'c>(
+    name: &str,
+    candidates: impl IntoIterator<Item = &'c str>,
+) -> Option<&'c str> {
+    // Same threshold as `cargo`
+    let max = std::cmp::max(name.chars().count(), 3) / 3;
+    candidates
+        .into_iter()
+        .map(|candidate| (edit_distance(name, candidate), candidate))
+        .filter(|(distance, _)| *distance <= max)
+        .min_by_key(|(distance, _)| *distance)
+        .map(|(_, candidate)| candidate)
+}
+
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut prev: Vec<usize> = (0..=b.len()).collect();
+    for (i, a) in a.chars().enumerate() {
+        let mut current = vec![i + 1];
+        for (j, b) in b.iter().enumerate() {
+            let substitute = prev[j] + if a == *b { 0 } else { 1 };
+            current.push(substitute.min(prev[j + 1] + 1).min(current[j] + 1));
+        }
+        prev = current;
+    }
+    prev[b.len()]
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn display_layout() {
+        let err = Error::PackageNotFound {
+            spec: "serd".to_owned(),
+            similar: Some("serde".to_owned()),
+        };
+        assert_eq!(
+            err.to_string(),
+            "package ID specification `serd` did not match any packages
+help: a package with a similar name exists: `serde`"
+        );
+        assert_eq!(err.exit_code(), 101);
+
+        let err = Error::FeatureNotFound {
+            feature: "st".to_owned(),
+            packages: vec!["a".to_owned(), "b".to_owned()],
+            similar: None,
+        };
+        assert_eq!(
+            err.to_string(),
+            "none of the selected packages contains the feature `st`
+note: selected packages: a, b"
+        );
+    }
+
+    #[cfg(feature = "cargo_metadata")]
+    #[test]
+    fn metadata_stderr_is_a_note() {
+        let err = Error::from(cargo_metadata::Error::CargoMetadata {
+            stderr: "error: failed to parse manifest\n".to_owned(),
+        });
+        assert_eq!(
+            err.to_string(),
+            "`cargo metadata` exited with an error
+note: failed to parse manifest"
+        );
+    }
+
+    #[test]
+    fn metadata_format() {
+        let err = Error::MetadataFormat {
+            path: path::PathBuf::from("metadata.json"),
+            found: Some(2),
+            supported: 1,
+        };
+        assert_eq!(
+            err.to_string(),
+            "unsupported `cargo metadata` format version 2 in `metadata.json`
+note: expected format version 1
+help: generate it with `cargo metadata --format-version 1`"
+        );
+    }
+
+    #[test]
+    fn similar() {
+        assert_eq!(similar_name("serd", ["serde", "clap"]), Some("serde"));
+        assert_eq!(similar_name("xyz", ["serde", "clap"]), None);
+        assert_eq!(edit_distance("kitten", "sitting"), 3);
+    }
+}
@@ -0,0 +1,437 @@
+//! Running an operation on each selected package in parallel.
+
+use std::collections;
+use std::panic;
+use std::sync;
+
+/// Run an operation per package, like `cargo` runs compilation units.
+///
+/// ```rust,no_run
+/// # let metadata = cargo_metadata::MetadataCommand::new().exec().unwrap();
+/// let workspace = clap_cargo::Workspace::default();
+/// let compilation = clap_cargo::Compilation::default();
+/// let (selected, _) = workspace.partition_packages(&metadata);
+/// let report = clap_cargo::Executor::new(&compilation)
+///     .run(&metadata, &selected, |package| -> Result<(), String> {
+///         println!("Checking {}", package.name);
+///         Ok(())
+///     })
+///     .unwrap();
+/// assert!(report.is_success());
+/// ```
+///
+/// Note: Requires the feature `cargo_metadata`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct Executor {
+    /// Maximum number of packages to process at once
+    pub jobs: usize,
+    /// Keep processing packages after one fails
+    pub keep_going: bool,
+    /// Wait for a package's workspace dependencies to finish before processing it
+    pub order: Option<crate::OrderOptions>,
+}
+
+impl Default for Executor {
+    fn default() -> Self {
+        Self::new(&Default::default())
+    }
+}
+
+impl Executor {
+    /// Limit parallelism with `--jobs` and stop on failure unless `--keep-going`.
+    pub fn new(compilation: &crate::Compilation) -> Self {
+        Self {
+            jobs: compilation.jobs(),
+            keep_going: compilation.keep_going,
+            order: None,
+        }
+    }
+
+    /// Process packages only after their workspace dependencies succeed.
+    pub fn ordered(mut self, options: crate::OrderOptions) -> Self {
+        self.order = Some(options);
+        self
+    }
+
+    /// Call `op` for each of `packages`, in parallel.
+    ///
+    /// Packages are [`Outcome::Skipped`] when a package they depend on fails (when
+    /// [ordered][Executor::ordered]) or when another package fails (without `keep_going`).
+    /// If `op` panics, the panic is resumed once all in-flight packages finish.
+    ///
+    /// When [ordered][Executor::ordered] without dependency information
+    /// (`MetadataCommand::no_deps`), packages have no dependencies to wait on.
+    pub fn run<'m, T, E, F>(
+        &self,
+        meta: &'m cargo_metadata::Metadata,
+        packages: &[&'m cargo_metadata::Package],
+        op: F,
+    ) -> Result<ExecutionReport<'m, T, E>, crate::CycleError>
+    where
+        F: Fn(&'m cargo_metadata::Package) -> Result<T, E> + Sync,
+        T: Send,
+        E: Send,
+    {
+        let prerequisites = match self.order.as_ref() {
+            Some(options) => {
+                let mut by_id = crate::order::prerequisites(meta, packages, options)?;
+                packages
+                    .iter()
+                    .map(|p| {
+                        by_id
+                            .remove(&p.id)
+                            .map(|deps| {
+                                deps.iter()
+                                    .filter_map(|dep| packages.iter().position(|p| p.id == **dep))
+                                    .collect()
+                            })
+                            .unwrap_or_default()
+                    })
+                    .collect()
+            }
+            None => vec![Vec::new(); packages.len()],
+        };
+
+        let state = sync::Mutex::new(State {
+            tasks: prerequisites
+                .into_iter()
+                .map(|prerequisites| Task {
+                    prerequisites,
+                    outcome: None,
+                })
+                .collect(),
+            running: 0,
+            stopped: false,
+            panic: None,
+        });
+        let changed = sync::Condvar::new();
+
+        let workers = self.jobs.max(1).min(packages.len());
+        crossbeam_utils::thread::scope(|scope| {
+            for _ in 0..workers {
+                scope.spawn(|_| self.work(packages, &op, &state, &changed));
+            }
+        })
+        .expect("panics are caught");
+
+        let state = state.into_inner().expect("panics are caught");
+        if let Some(payload) = state.panic {
+            panic::resume_unwind(payload);
+        }
+        let outcomes = packages
+            .iter()
+            .copied()
+            .zip(state.tasks.into_iter().map(|task| match task.outcome {
+                Some(Slot::Done(outcome)) => outcome,
+                _ => Outcome::Skipped,
+            }))
+            .collect();
+        Ok(ExecutionReport { outcomes })
+    }
+
+    fn work<'m, T, E, F>(
+        &self,
+        packages: &[&'m cargo_metadata::Package],
+        op: &F,
+        state: &sync::Mutex<State<T, E>>,
+        changed: &sync::Condvar,
+    ) where
+        F: Fn(&'m cargo_metadata::Package) -> Result<T, E> + Sync,
+    {
+        let mut guard = state.lock().expect("panics are caught");
+        loop {
+            let next = if guard.stopped {
+                None
+            } else {
+                guard.next_ready()
+            };
+            let index = match next {
+                Some(index) => index,
+                None if guard.running == 0 || guard.tasks.iter().all(|t| t.outcome.is_some()) => {
+                    changed.notify_all();
+                    return;
+                }
+                None => {
+                    guard = changed.wait(guard).expect("panics are caught");
+                    continue;
+                }
+            };
+
+            guard.tasks[index].outcome = Some(Slot::Running);
+            guard.running += 1;
+            drop(guard);
+            let result = panic::catch_unwind(panic::AssertUnwindSafe(|| op(packages[index])));
+            guard = state.lock().expect("panics are caught");
+            guard.running -= 1;
+
+            let outcome = match result {
+                Ok(Ok(value)) => Outcome::Success(value),
+                Ok(Err(err)) => Outcome::Failure(err),
+                Err(payload) => {
+                    guard.panic.get_or_insert(payload);
+                    guard.stopped = true;
+                    Outcome::Skipped
+                }
+            };
+            if !matches!(outcome, Outcome::Success(_)) {
+                guard.skip_dependents(index);
+                if !self.keep_going {
+                    guard.stopped = true;
+                }
+            }
+            guard.tasks[index].outcome = Some(Slot::Done(outcome));
+            changed.notify_all();
+        }
+    }
+}
+
+struct State<T, E> {
+    tasks: Vec<Task<T, E>>,
+    running: usize,
+    stopped: bool,
+    panic: Option<Box<dyn std::any::Any + Send + 'static>>,
+}
+
+struct Task<T, E> {
+    prerequisites: Vec<usize>,
+    outcome: Option<Slot<T, E>>,
+}
+
+enum Slot<T, E> {
+    Running,
+    Done(Outcome<T, E>),
+}
+
+impl<T, E> State<T, E> {
+    fn next_ready(&self) -> Option<usize> {
+        self.tasks.iter().position(|task| {
+            task.outcome.is_none()
+                && task.prerequisites.iter().all(|dep| {
+                    matches!(
+                        self.tasks[*dep].outcome,
+                        Some(Slot::Done(Outcome::Success(_)))
+                    )
+                })
+        })
+    }
+
+    fn skip_dependents(&mut self, failed: usize) {
+        let mut pending = vec![failed];
+        let mut visited = collections::HashSet::new();
+        while let Some(current) = pending.pop() {
+            for (index, task) in self.tasks.iter_mut().enumerate() {
+                if task.outcome.is_none()
+                    && task.prerequisites.contains(&current)
+                    && visited.insert(index)
+                {
+                    task.outcome = Some(Slot::Done(Outcome::Skipped));
+                    pending.push(index);
+                }
+            }
+        }
+    }
+}
+
+/// Result of processing a package with an [`Executor`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Outcome<T, E> {
+    /// The operation succeeded
+    Success(T),
+    /// The operation failed
+    Failure(E),
+    /// The operation was not run because of an earlier failure
+    Skipped,
+}
+
+/// Outcomes from [`Executor::run`], in the order the packages were given.
+#[derive(Debug)]
+#[non_exhaustive]
+pub struct ExecutionReport<'m, T, E> {
+    pub outcomes: Vec<(&'m cargo_metadata::Package, Outcome<T, E>)>,
+}
+
+impl<'m, T, E> ExecutionReport<'m, T, E> {
+    /// Whether every package succeeded
+    pub fn is_success(&self) -> bool {
+        self.outcomes
+            .iter()
+            .all(|(_, outcome)| matches!(outcome, Outcome::Success(_)))
+    }
+
+    /// Packages that failed and why
+    pub fn failures(&self) -> impl Iterator<Item = (&'m cargo_metadata::Package, &E)> + '_ {
+        self.outcomes
+            .iter()
+            .filter_map(|(package, outcome)| match outcome {
+                Outcome::Failure(err) => Some((*package, err)),
+                _ => None,
+            })
+    }
+
+    /// Packages that were not processed because of a failure
+    pub fn skipped(&self) -> impl Iterator<Item = &'m cargo_metadata::Package> + '_ {
+        self.outcomes
+            .iter()
+            .filter(|(_, outcome)| matches!(outcome, Outcome::Skipped))
+            .map(|(package, _)| *package)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn metadata() -> cargo_metadata::Metadata {
+        let mut metadata = cargo_metadata::MetadataCommand::new();
+        metadata.manifest_path("tests/fixtures/graph/Cargo.toml");
+        metadata.exec().unwrap()
+    }
+
+    fn members(meta: &cargo_metadata::Metadata) -> Vec<&cargo_metadata::Package> {
+        let mut packages: Vec<_> = meta
+            .packages
+            .iter()
+            .filter(|p| meta.workspace_members.contains(&p.id))
+            .collect();
+        packages.sort_by(|a, b| a.name.cmp(&b.name));
+        packages
+    }
+
+    fn executor(jobs: usize, keep_going: bool) -> Executor {
+        Executor {
+            jobs,
+            keep_going,
+            order: None,
+        }
+    }
+
+    #[test]
+    fn unordered() {
+        let meta = metadata();
+        let packages = members(&meta);
+        let report = executor(4, false)
+            .run(&meta, &packages, |p| Ok::<_, ()>(p.name.len()))
+            .unwrap();
+        assert!(report.is_success());
+        let lens: Vec<_> = report
+            .outcomes
+            .iter()
+            .map(|(p, outcome)| (p.name.as_str(), outcome.clone()))
+            .collect();
+        assert_eq!(
+            lens,
+            [
+                ("app", Outcome::Success(3)),
+                ("base", Outcome::Success(4)),
+                ("macros", Outcome::Success(6)),
+                ("testing", Outcome::Success(7)),
+                ("tool", Outcome::Success(4)),
+            ]
+        );
+    }
+
+    #[test]
+    fn ordered() {
+        let meta = metadata();
+        let packages = members(&meta);
+        let finished = sync::Mutex::new(Vec::new());
+        let report = executor(4, false)
+            .ordered(Default::default())
+            .run(&meta, &packages, |p| {
+                finished.lock().unwrap().push(p.name.clone());
+                Ok::<_, ()>(())
+            })
+            .unwrap();
+        assert!(report.is_success());
+
+        let finished = finished.into_inner().unwrap();
+        let position = |name: &str| finished.iter().position(|n| n == name).unwrap();
+        assert!(position("base") < position("macros"));
+        assert!(position("macros") < position("app"));
+        assert!(position("base") < position("tool"));
+    }
+
+    #[test]
+    fn ordered_failure_skips_dependents() {
+        let meta = metadata();
+        let packages = members(&meta);
+        let report = executor(1, true)
+            .ordered(Default::default())
+            .run(&meta, &packages, |p| {
+                if p.name == "macros" {
+                    Err("failed")
+                } else {
+                    Ok(())
+                }
+            })
+            .unwrap();
+        assert!(!report.is_success());
+        let failures: Vec<_> = report
+            .failures()
+            .map(|(p, e)| (p.name.as_str(), *e))
+            .collect();
+        assert_eq!(failures, [("macros", "failed")]);
+        let skipped: Vec<_> = report.skipped().map(|p| p.name.as_str()).collect();
+        assert_eq!(skipped, ["app"]);
+    }
+
+    #[test]
+    fn stop_on_failure() {
+        let meta = metadata();
+        let packages = members(&meta);
+        let report = executor(1, false)
+            .run(&meta, &packages, |p| {
+                if p.name == "base" {
+                    Err(())
+                } else {
+                    Ok(())
+                }
+            })
+            .unwrap();
+        let skipped: Vec<_> = report.skipped().map(|p| p.name.as_str()).collect();
+        assert_eq!(skipped, ["macros", "testing", "tool"]);
+    }
+
+    #[test]
+    fn keep_going() {
+        let meta = metadata();
+        let packages = members(&meta);
+        let report = executor(2, true)
+            .run(&meta, &packages, |p| {
+                if p.name == "base" {
+                    Err(())
+                } else {
+                    Ok(())
+                }
+            })
+            .unwrap();
+        assert_eq!(report.failures().count(), 1);
+        assert_eq!(report.skipped().count(), 0);
+    }
+
+    #[test]
+    #[should_panic(expected = "boom")]
+    fn panics_resume() {
+        let meta = metadata();
+        let packages = members(&meta);
+        let _ = executor(2, true).run(&meta, &packages, |p| -> Result<(), ()> {
+            if p.name == "base" {
+                panic!("boom");
+            }
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn ordered_cycle() {
+        let meta = metadata();
+        let packages = members(&meta);
+        let res = executor(2, true)
+            .ordered(crate::OrderOptions {
+                dev_dependencies: true,
+            })
+            .run(&meta, &packages, |_| Ok::<_, ()>(()));
+        assert!(res.is_err());
+    }
+}
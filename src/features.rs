@@ -1,19 +1,37 @@
 //! Cargo Feature Flags.
 
-#[derive(Default, Clone, Debug, PartialEq, Eq, clap::Args)]
+#[derive(Default, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "kebab-case", default))]
 #[non_exhaustive]
 pub struct Features {
-    #[clap(long)]
     /// Activate all available features
     pub all_features: bool,
-    #[clap(long)]
     /// Do not activate the `default` feature
     pub no_default_features: bool,
-    #[clap(long, require_value_delimiter = true, value_delimiter = ' ')]
     /// Space-separated list of features to activate
     pub features: Vec<String>,
 }
 
+impl Features {
+    /// Fill in anything not selected in `self` from `defaults`.
+    ///
+    /// - `--all-features` / `--no-default-features` are enabled if either enables them.  These
+    ///   flags have no negative form, so a default of `true` cannot be turned off from the
+    ///   command-line.
+    /// - `--features` from both are combined
+    pub fn with_defaults(mut self, defaults: &Features) -> Self {
+        self.all_features |= defaults.all_features;
+        self.no_default_features |= defaults.no_default_features;
+        for feature in &defaults.features {
+            if !self.features.contains(feature) {
+                self.features.push(feature.clone());
+            }
+        }
+        self
+    }
+}
+
 #[cfg(feature = "cargo_metadata")]
 impl Features {
     /// Forward these flags to the `cargo_metadata` crate.
@@ -38,6 +56,265 @@ impl Features {
     }
 }
 
+impl Features {
+    /// Features of the package `id` enabled by these flags.
+    ///
+    /// Includes features enabled transitively through other features of the same package but not
+    /// features of its dependencies.  `--features pkg/feat` only applies to the package named
+    /// `pkg`.  Unknown features are ignored, see [`Features::validate`].
+    pub fn resolve<G: crate::WorkspaceGraph>(
+        &self,
+        graph: &G,
+        id: &G::Id,
+    ) -> std::collections::BTreeSet<String> {
+        let name = graph.package_name(id);
+        let declared: std::collections::HashMap<_, _> = graph.features(id).into_iter().collect();
+
+        let mut pending: Vec<&str> = Vec::new();
+        if self.all_features {
+            pending.extend(declared.keys().copied());
+        }
+        if !self.no_default_features {
+            pending.push("default");
+        }
+        for value in self.features.iter().flat_map(|f| f.split(',')) {
+            let value = value.trim();
+            match value.split_once('/') {
+                Some((package, feature)) if package == name => pending.push(feature),
+                _ if !value.is_empty() => pending.push(value),
+                _ => {}
+            }
+        }
+
+        let mut enabled = std::collections::BTreeSet::new();
+        while let Some(value) = pending.pop() {
+            let feature = if value.starts_with("dep:") {
+                // Enables an optional dependency without a feature of the same name
+                continue;
+            } else if let Some((feature, _)) = value.split_once('/') {
+                // Enables a feature of a dependency; `dep?/feat` does not enable `dep` itself
+                if feature.ends_with('?') {
+                    continue;
+                }
+                feature
+            } else {
+                value
+            };
+            if let Some((feature, values)) = declared.get_key_value(feature) {
+                if enabled.insert((*feature).to_owned()) {
+                    pending.extend(values.iter().copied());
+                }
+            }
+        }
+        enabled
+    }
+
+    /// Check that each `--features` entry exists in at least one of the packages `ids`, like
+    /// `cargo`.
+    ///
+    /// `pkg/feat` is only checked when `pkg` is one of `ids`; features of dependencies are not
+    /// checked.
+    pub fn validate<G: crate::WorkspaceGraph>(
+        &self,
+        graph: &G,
+        ids: &[&G::Id],
+    ) -> Result<(), crate::Error> {
+        let packages: Vec<(&str, Vec<&str>)> = ids
+            .iter()
+            .map(|id| {
+                let features = graph.features(id).into_iter().map(|(name, _)| name);
+                (graph.package_name(id), features.collect())
+            })
+            .collect();
+
+        for value in self.features.iter().flat_map(|f| f.split(',')) {
+            let value = value.trim();
+            if value.is_empty() || value.starts_with("dep:") {
+                continue;
+            }
+            let (checked, feature): (Vec<_>, _) = match value.split_once('/') {
+                Some((package, feature)) => {
+                    let checked: Vec<_> = packages.iter().filter(|(n, _)| *n == package).collect();
+                    if checked.is_empty() {
+                        // A dependency's feature
+                        continue;
+                    }
+                    (checked, feature)
+                }
+                None => (packages.iter().collect(), value),
+            };
+            if !checked
+                .iter()
+                .any(|(_, features)| features.contains(&feature))
+            {
+                let similar = crate::error::similar_name(
+                    feature,
+                    checked
+                        .iter()
+                        .flat_map(|(_, features)| features.iter().copied()),
+                );
+                return Err(crate::Error::FeatureNotFound {
+                    feature: feature.to_owned(),
+                    packages: checked.iter().map(|(name, _)| (*name).to_owned()).collect(),
+                    similar: similar.map(ToOwned::to_owned),
+                });
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(feature = "cargo_metadata")]
+impl From<cargo_metadata::CargoOpt> for Features {
+    fn from(opt: cargo_metadata::CargoOpt) -> Self {
+        std::iter::once(opt).collect()
+    }
+}
+
+#[cfg(feature = "cargo_metadata")]
+impl std::iter::FromIterator<cargo_metadata::CargoOpt> for Features {
+    fn from_iter<I: IntoIterator<Item = cargo_metadata::CargoOpt>>(opts: I) -> Self {
+        let mut features = Features::default();
+        features.extend(opts);
+        features
+    }
+}
+
+#[cfg(feature = "cargo_metadata")]
+impl Extend<cargo_metadata::CargoOpt> for Features {
+    fn extend<I: IntoIterator<Item = cargo_metadata::CargoOpt>>(&mut self, opts: I) {
+        for opt in opts {
+            match opt {
+                cargo_metadata::CargoOpt::AllFeatures => self.all_features = true,
+                cargo_metadata::CargoOpt::NoDefaultFeatures => self.no_default_features = true,
+                cargo_metadata::CargoOpt::SomeFeatures(features) => self.features.extend(features),
+            }
+        }
+    }
+}
+
+#[cfg(feature = "escargot")]
+impl Features {
+    /// Forward these flags to an `escargot::CargoBuild`.
+    ///
+    /// Note: Requires the features `escargot`.
+    pub fn forward_escargot(&self, mut cmd: escargot::CargoBuild) -> escargot::CargoBuild {
+        if self.all_features {
+            cmd = cmd.all_features();
+        }
+        if self.no_default_features {
+            cmd = cmd.no_default_features();
+        }
+        if !self.features.is_empty() {
+            // `CargoBuild::features` passes `-features`
+            cmd = cmd.arg("--features").arg(self.features.join(" "));
+        }
+        cmd
+    }
+}
+
+crate::args::flag_group_args!(FeaturesArgs for Features {
+    "all-features",
+    "no-default-features",
+    "features",
+});
+
+impl FeaturesArgs {
+    /// Register the flags on `cmd`.
+    pub fn augment_args<'help>(&'help self, cmd: clap::Command<'help>) -> clap::Command<'help> {
+        let names = &self.names;
+        cmd.arg(
+            names
+                .arg("all-features")
+                .help("Activate all available features"),
+        )
+        .arg(
+            names
+                .arg("no-default-features")
+                .help("Do not activate the `default` feature"),
+        )
+        .arg(
+            names
+                .arg("features")
+                .takes_value(true)
+                .multiple_occurrences(true)
+                .require_value_delimiter(true)
+                .value_delimiter(' ')
+                .help("Space-separated list of features to activate"),
+        )
+    }
+
+    /// Read the flags registered by [`FeaturesArgs::augment_args`] from `matches`.
+    pub fn from_arg_matches(&self, matches: &clap::ArgMatches) -> Features {
+        let names = &self.names;
+        Features {
+            all_features: names.flag(matches, "all-features"),
+            no_default_features: names.flag(matches, "no-default-features"),
+            features: names.values(matches, "features"),
+        }
+    }
+}
+
+impl Features {
+    /// Build [`Features`] flags programmatically.
+    ///
+    /// ```rust
+    /// let features = clap_cargo::Features::builder()
+    ///     .no_default_features(true)
+    ///     .feature("std")
+    ///     .build();
+    /// assert_eq!(features.features, ["std"]);
+    /// ```
+    pub fn builder() -> FeaturesBuilder {
+        FeaturesBuilder::default()
+    }
+}
+
+/// Builder for [`Features`], see [`Features::builder`].
+#[derive(Default, Clone, Debug, PartialEq, Eq)]
+pub struct FeaturesBuilder {
+    inner: Features,
+}
+
+impl FeaturesBuilder {
+    /// Activate all available features
+    pub fn all_features(mut self, yes: bool) -> Self {
+        self.inner.all_features = yes;
+        self
+    }
+
+    /// Do not activate the `default` feature
+    pub fn no_default_features(mut self, yes: bool) -> Self {
+        self.inner.no_default_features = yes;
+        self
+    }
+
+    /// Activate `feature`, in addition to earlier ones
+    pub fn feature(mut self, feature: impl Into<String>) -> Self {
+        self.inner.features.push(feature.into());
+        self
+    }
+
+    /// Activate each of `features`, in addition to earlier ones
+    pub fn features(mut self, features: impl IntoIterator<Item = impl Into<String>>) -> Self {
+        self.inner
+            .features
+            .extend(features.into_iter().map(Into::into));
+        self
+    }
+
+    /// Finish building the flags
+    pub fn build(self) -> Features {
+        self.inner
+    }
+}
+
+impl From<FeaturesBuilder> for Features {
+    fn from(builder: FeaturesBuilder) -> Self {
+        builder.build()
+    }
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -133,6 +410,26 @@ mod test {
         );
     }
 
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serde_kebab_case() {
+        let features: Features = serde_json::from_str(
+            r#"{"all-features": true, "no-default-features": true, "features": ["foo"]}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            features,
+            Features {
+                all_features: true,
+                no_default_features: true,
+                features: vec!["foo".to_owned()],
+            }
+        );
+
+        let features: Features = serde_json::from_str("{}").unwrap();
+        assert_eq!(features, Features::default());
+    }
+
     #[cfg(feature = "cargo_metadata")]
     #[test]
     fn features_all() {
@@ -162,4 +459,194 @@ mod test {
         metadata.exec().unwrap();
         // TODO verify we forwarded correctly.
     }
+
+    #[test]
+    fn validate() {
+        use crate::backend::test::{MemoryGraph, MemoryPackage};
+
+        let graph = MemoryGraph {
+            packages: vec![
+                MemoryPackage {
+                    id: 1,
+                    name: "app",
+                    features: vec![("std", vec![]), ("serde", vec!["dep:serde"])],
+                    ..Default::default()
+                },
+                MemoryPackage {
+                    id: 2,
+                    name: "lib",
+                    features: vec![("alloc", vec![])],
+                    ..Default::default()
+                },
+            ],
+            members: vec![1, 2],
+            root: None,
+        };
+        let validate = |features: &[&str]| {
+            Features {
+                features: features.iter().map(|f| (*f).to_owned()).collect(),
+                ..Default::default()
+            }
+            .validate(&graph, &[&1, &2])
+        };
+
+        validate(&[
+            "std,alloc",
+            "app/serde",
+            "lib/alloc",
+            "other/anything",
+            "dep:x",
+        ])
+        .unwrap();
+        match validate(&["stf"]).unwrap_err() {
+            crate::Error::FeatureNotFound {
+                feature,
+                packages,
+                similar,
+            } => {
+                assert_eq!(feature, "stf");
+                assert_eq!(packages, ["app", "lib"]);
+                assert_eq!(similar.as_deref(), Some("std"));
+            }
+            err => panic!("unexpected error: {}", err),
+        }
+        assert_eq!(
+            validate(&["lib/std"]).unwrap_err().to_string(),
+            "package `lib` does not have the feature `std`"
+        );
+    }
+
+    #[test]
+    fn resolve() {
+        use crate::backend::test::{MemoryGraph, MemoryPackage};
+
+        let graph = MemoryGraph {
+            packages: vec![MemoryPackage {
+                id: 1,
+                name: "app",
+                features: vec![
+                    ("default", vec!["std"]),
+                    ("std", vec!["alloc", "serde?/std"]),
+                    ("alloc", vec![]),
+                    ("serde", vec!["dep:serde"]),
+                    ("derive", vec!["serde/derive"]),
+                    ("unused", vec![]),
+                ],
+                ..Default::default()
+            }],
+            members: vec![1],
+            root: Some(1),
+        };
+        let resolve = |features: Features| -> Vec<String> {
+            features.resolve(&graph, &1).into_iter().collect()
+        };
+
+        assert_eq!(resolve(Features::default()), ["alloc", "default", "std"]);
+        assert_eq!(
+            resolve(Features {
+                no_default_features: true,
+                ..Default::default()
+            }),
+            Vec::<String>::new()
+        );
+        assert_eq!(
+            resolve(Features {
+                no_default_features: true,
+                features: vec!["app/derive".to_owned(), "other/std".to_owned()],
+                ..Default::default()
+            }),
+            ["derive", "serde"]
+        );
+        assert_eq!(
+            resolve(Features {
+                no_default_features: true,
+                features: vec!["alloc,missing".to_owned()],
+                ..Default::default()
+            }),
+            ["alloc"]
+        );
+        assert_eq!(
+            resolve(Features {
+                all_features: true,
+                ..Default::default()
+            }),
+            ["alloc", "default", "derive", "serde", "std", "unused"]
+        );
+    }
+
+    #[cfg(feature = "escargot")]
+    #[test]
+    fn forward_escargot() {
+        let cmd = escargot::CargoBuild::new().manifest_path("tests/fixtures/simple/Cargo.toml");
+        let features = Features {
+            no_default_features: true,
+            ..Default::default()
+        };
+        crate::test_support::build(features.forward_escargot(cmd)).unwrap();
+
+        let cmd = escargot::CargoBuild::new().manifest_path("tests/fixtures/simple/Cargo.toml");
+        let features = Features {
+            features: vec!["missing".to_owned()],
+            ..Default::default()
+        };
+        crate::test_support::build(features.forward_escargot(cmd)).unwrap_err();
+    }
+
+    #[test]
+    fn args_match_derive() {
+        let args = FeaturesArgs::new();
+        let cmd = args.augment_args(clap::Command::new("test"));
+        cmd.clone().debug_assert();
+
+        let argv = ["test", "--features", "foo bar", "--features", "baz"];
+        let matches = cmd.try_get_matches_from(argv).unwrap();
+        #[derive(Debug, StructOpt)]
+        struct Args {
+            #[clap(flatten)]
+            features: Features,
+        }
+        assert_eq!(
+            args.from_arg_matches(&matches),
+            Args::parse_from(argv).features
+        );
+    }
+
+    #[test]
+    fn builder() {
+        let features = Features::builder()
+            .no_default_features(true)
+            .feature("a")
+            .features(vec!["b".to_owned()])
+            .build();
+        assert_eq!(
+            features,
+            Features {
+                no_default_features: true,
+                features: vec!["a".to_owned(), "b".to_owned()],
+                ..Default::default()
+            }
+        );
+    }
+
+    #[cfg(feature = "cargo_metadata")]
+    #[test]
+    fn from_cargo_opt() {
+        assert_eq!(
+            Features::from(cargo_metadata::CargoOpt::AllFeatures),
+            Features::builder().all_features(true).build()
+        );
+        let features: Features = vec![
+            cargo_metadata::CargoOpt::NoDefaultFeatures,
+            cargo_metadata::CargoOpt::SomeFeatures(vec!["a".to_owned()]),
+        ]
+        .into_iter()
+        .collect();
+        assert_eq!(
+            features,
+            Features::builder()
+                .no_default_features(true)
+                .feature("a")
+                .build()
+        );
+    }
 }
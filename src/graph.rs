@@ -0,0 +1,221 @@
+//! Walking the dependency graph between packages.
+
+use std::collections;
+use std::hash::Hash;
+
+/// Limits on walking a [`DependencyGraph`].
+#[derive(Default, Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct WalkOptions {
+    /// Maximum number of edges to follow from a starting package, unlimited when `None`
+    pub depth: Option<usize>,
+    /// Only follow edges of these kinds, all kinds when empty
+    pub kinds: Vec<crate::EdgeKind>,
+}
+
+/// Dependency edges from `Metadata::resolve` or another [`WorkspaceGraph`][crate::WorkspaceGraph],
+/// walkable in either direction.
+#[derive(Clone, Debug)]
+pub struct DependencyGraph<'g, I> {
+    dependencies: collections::HashMap<&'g I, Vec<Edge<'g, I>>>,
+    dependents: collections::HashMap<&'g I, Vec<Edge<'g, I>>>,
+}
+
+#[derive(Clone, Debug)]
+struct Edge<'g, I> {
+    id: &'g I,
+    kinds: Vec<crate::EdgeKind>,
+}
+
+impl<I> Edge<'_, I> {
+    fn follows(&self, kinds: &[crate::EdgeKind]) -> bool {
+        kinds.is_empty() || self.kinds.iter().any(|k| kinds.contains(k))
+    }
+}
+
+#[cfg(feature = "cargo_metadata")]
+impl<'m> DependencyGraph<'m, cargo_metadata::PackageId> {
+    /// Index the resolved dependency graph.
+    ///
+    /// Without dependency information (`MetadataCommand::no_deps`), the graph has no edges; check
+    /// for it with [`Workspace::validate`][crate::Workspace::validate].
+    ///
+    /// Note: Requires the feature `cargo_metadata`.
+    pub fn new(meta: &'m cargo_metadata::Metadata) -> Self {
+        let mut graph = Self::default();
+        for node in meta.resolve.iter().flat_map(|r| r.nodes.iter()) {
+            for dep in &node.deps {
+                graph.add(&node.id, &dep.pkg, crate::EdgeKind::of(dep));
+            }
+        }
+        graph
+    }
+}
+
+impl<'g, I: Eq + Hash> DependencyGraph<'g, I> {
+    /// Index the dependencies of every package in `graph`.
+    pub fn from_graph<G: crate::WorkspaceGraph<Id = I>>(graph: &'g G) -> Self {
+        let mut dependency_graph = Self::default();
+        for id in graph.package_ids() {
+            for (dep, kinds) in graph.dependencies(id) {
+                dependency_graph.add(id, dep, kinds);
+            }
+        }
+        dependency_graph
+    }
+
+    fn add(&mut self, id: &'g I, dep: &'g I, kinds: Vec<crate::EdgeKind>) {
+        self.dependencies.entry(id).or_default().push(Edge {
+            id: dep,
+            kinds: kinds.clone(),
+        });
+        self.dependents
+            .entry(dep)
+            .or_default()
+            .push(Edge { id, kinds });
+    }
+
+    /// Packages `id` directly depends on through any of `kinds`, all kinds when empty.
+    pub fn direct_dependencies<'s>(
+        &'s self,
+        id: &I,
+        kinds: &'s [crate::EdgeKind],
+    ) -> impl Iterator<Item = &'g I> + 's {
+        self.dependencies
+            .get(id)
+            .into_iter()
+            .flatten()
+            .filter(move |edge| edge.follows(kinds))
+            .map(|edge| edge.id)
+    }
+
+    /// `seeds` and the packages they transitively depend on.
+    pub fn dependencies(
+        &self,
+        seeds: impl IntoIterator<Item = &'g I>,
+        options: &WalkOptions,
+    ) -> collections::HashSet<&'g I> {
+        walk(&self.dependencies, seeds, options)
+    }
+
+    /// `seeds` and the packages that transitively depend on them.
+    pub fn dependents(
+        &self,
+        seeds: impl IntoIterator<Item = &'g I>,
+        options: &WalkOptions,
+    ) -> collections::HashSet<&'g I> {
+        walk(&self.dependents, seeds, options)
+    }
+}
+
+impl<I> Default for DependencyGraph<'_, I> {
+    fn default() -> Self {
+        Self {
+            dependencies: collections::HashMap::new(),
+            dependents: collections::HashMap::new(),
+        }
+    }
+}
+
+fn walk<'g, I: Eq + Hash>(
+    edges: &collections::HashMap<&'g I, Vec<Edge<'g, I>>>,
+    seeds: impl IntoIterator<Item = &'g I>,
+    options: &WalkOptions,
+) -> collections::HashSet<&'g I> {
+    let mut visited = collections::HashSet::new();
+    let mut pending: collections::VecDeque<_> = seeds.into_iter().map(|id| (id, 0)).collect();
+    while let Some((id, depth)) = pending.pop_front() {
+        if !visited.insert(id) {
+            continue;
+        }
+        if options.depth.map(|max| max <= depth).unwrap_or(false) {
+            continue;
+        }
+        if let Some(next) = edges.get(id) {
+            pending.extend(
+                next.iter()
+                    .filter(|edge| edge.follows(&options.kinds))
+                    .map(|edge| (edge.id, depth + 1)),
+            );
+        }
+    }
+    visited
+}
+
+#[cfg(feature = "cargo_metadata")]
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn metadata(manifest_path: &str) -> cargo_metadata::Metadata {
+        let mut metadata = cargo_metadata::MetadataCommand::new();
+        metadata.manifest_path(manifest_path);
+        metadata.exec().unwrap()
+    }
+
+    fn id<'m>(meta: &'m cargo_metadata::Metadata, name: &str) -> &'m cargo_metadata::PackageId {
+        &meta.packages.iter().find(|p| p.name == name).unwrap().id
+    }
+
+    fn names(
+        meta: &cargo_metadata::Metadata,
+        ids: &collections::HashSet<&cargo_metadata::PackageId>,
+    ) -> Vec<String> {
+        let mut names: Vec<_> = meta
+            .packages
+            .iter()
+            .filter(|p| ids.contains(&p.id))
+            .map(|p| p.name.clone())
+            .collect();
+        names.sort();
+        names
+    }
+
+    #[test]
+    fn dependents() {
+        let meta = metadata("tests/fixtures/graph/Cargo.toml");
+        let graph = DependencyGraph::new(&meta);
+        let ids = graph.dependents(vec![id(&meta, "base")], &WalkOptions::default());
+        assert_eq!(names(&meta, &ids), ["app", "base", "macros", "tool"]);
+    }
+
+    #[test]
+    fn dependencies() {
+        let meta = metadata("tests/fixtures/graph/Cargo.toml");
+        let graph = DependencyGraph::new(&meta);
+        let ids = graph.dependencies(vec![id(&meta, "app")], &WalkOptions::default());
+        assert_eq!(names(&meta, &ids), ["app", "base", "macros", "testing"]);
+    }
+
+    #[test]
+    fn depth() {
+        let meta = metadata("tests/fixtures/graph/Cargo.toml");
+        let graph = DependencyGraph::new(&meta);
+        let options = WalkOptions {
+            depth: Some(0),
+            ..Default::default()
+        };
+        let ids = graph.dependencies(vec![id(&meta, "app")], &options);
+        assert_eq!(names(&meta, &ids), ["app"]);
+        let options = WalkOptions {
+            depth: Some(1),
+            ..Default::default()
+        };
+        let ids = graph.dependencies(vec![id(&meta, "app")], &options);
+        assert_eq!(names(&meta, &ids), ["app", "macros", "testing"]);
+    }
+
+    #[test]
+    fn kinds() {
+        let meta = metadata("tests/fixtures/graph/Cargo.toml");
+        let graph = DependencyGraph::new(&meta);
+        let options = WalkOptions {
+            kinds: vec![crate::EdgeKind::Normal],
+            ..Default::default()
+        };
+        let ids = graph.dependencies(vec![id(&meta, "app")], &options);
+        assert_eq!(names(&meta, &ids), ["app", "base", "macros"]);
+        let ids = graph.dependents(vec![id(&meta, "base")], &options);
+        assert_eq!(names(&meta, &ids), ["app", "base", "macros"]);
+    }
+}
@@ -0,0 +1,409 @@
+//! Cargo flags for selecting what to install, like `cargo install`.
+
+use std::path;
+
+#[derive(Default, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "kebab-case", default))]
+#[non_exhaustive]
+pub struct Install {
+    /// Git URL to install the specified crate from
+    pub git: Option<String>,
+    /// Branch to use when installing from git
+    pub branch: Option<String>,
+    /// Tag to use when installing from git
+    pub tag: Option<String>,
+    /// Specific commit to use when installing from git
+    pub rev: Option<String>,
+    /// Filesystem path to local crate to install
+    pub path: Option<path::PathBuf>,
+    /// Specify a version to install
+    pub version: Option<String>,
+    /// Directory to install packages into
+    pub root: Option<path::PathBuf>,
+}
+
+/// Where [`Install`] should get the package from.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum InstallSource {
+    /// Install from the registry, optionally constrained to `version`
+    Registry { version: Option<String> },
+    /// Install from a git repository
+    Git {
+        url: String,
+        reference: GitReference,
+        version: Option<String>,
+    },
+    /// Install from a local crate
+    Path {
+        path: path::PathBuf,
+        version: Option<String>,
+    },
+}
+
+/// Revision to check out from a git repository.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum GitReference {
+    /// The repository's `HEAD`
+    DefaultBranch,
+    Branch(String),
+    Tag(String),
+    Rev(String),
+}
+
+impl Install {
+    /// Select the source to install from.
+    ///
+    /// Conflicting flags are rejected while parsing.  When constructed directly, `--git` takes
+    /// precedence over `--path` and `--branch` over `--tag` over `--rev`, like `cargo install`.
+    pub fn source(&self) -> InstallSource {
+        let version = self.version.clone();
+        if let Some(url) = self.git.as_ref() {
+            let reference = if let Some(branch) = self.branch.as_ref() {
+                GitReference::Branch(branch.clone())
+            } else if let Some(tag) = self.tag.as_ref() {
+                GitReference::Tag(tag.clone())
+            } else if let Some(rev) = self.rev.as_ref() {
+                GitReference::Rev(rev.clone())
+            } else {
+                GitReference::DefaultBranch
+            };
+            InstallSource::Git {
+                url: url.clone(),
+                reference,
+                version,
+            }
+        } else if let Some(path) = self.path.as_ref() {
+            InstallSource::Path {
+                path: path.clone(),
+                version,
+            }
+        } else {
+            InstallSource::Registry { version }
+        }
+    }
+}
+
+#[cfg(feature = "config")]
+impl Install {
+    /// Directory to install into, like `cargo install`.
+    ///
+    /// Order of precedence: `--root`, `CARGO_INSTALL_ROOT`, `install.root`, `$CARGO_HOME`.
+    ///
+    /// Note: Requires the feature `config`.
+    pub fn root(&self, config: &crate::CargoConfig) -> std::io::Result<path::PathBuf> {
+        if let Some(root) = self.root.as_ref() {
+            return Ok(config.cwd().join(root));
+        }
+        let root = config
+            .get_path("install.root")?
+            .unwrap_or_else(|| config.cargo_home().to_owned());
+        Ok(root)
+    }
+}
+
+crate::args::flag_group_args!(InstallArgs for Install {
+    "git",
+    "branch",
+    "tag",
+    "rev",
+    "path",
+    "version",
+    "vers",
+    "root",
+});
+
+impl InstallArgs {
+    /// Register the flags on `cmd`.
+    pub fn augment_args<'help>(&'help self, cmd: clap::Command<'help>) -> clap::Command<'help> {
+        let names = &self.names;
+        cmd.arg(
+            names
+                .arg("git")
+                .value_name("URL")
+                .conflicts_with(names.get("path"))
+                .help("Git URL to install the specified crate from"),
+        )
+        .arg(
+            names
+                .arg("branch")
+                .value_name("BRANCH")
+                .requires(names.get("git"))
+                .conflicts_with_all(&[names.get("tag"), names.get("rev")])
+                .help("Branch to use when installing from git"),
+        )
+        .arg(
+            names
+                .arg("tag")
+                .value_name("TAG")
+                .requires(names.get("git"))
+                .conflicts_with(names.get("rev"))
+                .help("Tag to use when installing from git"),
+        )
+        .arg(
+            names
+                .arg("rev")
+                .value_name("SHA")
+                .requires(names.get("git"))
+                .help("Specific commit to use when installing from git"),
+        )
+        .arg(
+            names
+                .arg("path")
+                .value_name("PATH")
+                .allow_invalid_utf8(true)
+                .help("Filesystem path to local crate to install"),
+        )
+        .arg(
+            names
+                .arg("version")
+                .value_name("VERSION")
+                .alias(names.long("vers"))
+                .help("Specify a version to install"),
+        )
+        .arg(
+            names
+                .arg("root")
+                .value_name("DIR")
+                .allow_invalid_utf8(true)
+                .help("Directory to install packages into"),
+        )
+    }
+
+    /// Read the flags registered by [`InstallArgs::augment_args`] from `matches`.
+    pub fn from_arg_matches(&self, matches: &clap::ArgMatches) -> Install {
+        let names = &self.names;
+        Install {
+            git: names.value(matches, "git"),
+            branch: names.value(matches, "branch"),
+            tag: names.value(matches, "tag"),
+            rev: names.value(matches, "rev"),
+            path: names.path(matches, "path"),
+            version: names.value(matches, "version"),
+            root: names.path(matches, "root"),
+        }
+    }
+}
+
+impl Install {
+    /// Build [`Install`] flags programmatically.
+    ///
+    /// ```rust
+    /// let install = clap_cargo::Install::builder().path("crates/tool").build();
+    /// assert!(matches!(install.source(), clap_cargo::InstallSource::Path { .. }));
+    /// ```
+    pub fn builder() -> InstallBuilder {
+        InstallBuilder::default()
+    }
+}
+
+/// Builder for [`Install`], see [`Install::builder`].
+#[derive(Default, Clone, Debug, PartialEq, Eq)]
+pub struct InstallBuilder {
+    inner: Install,
+}
+
+impl InstallBuilder {
+    /// Git URL to install the specified crate from
+    pub fn git(mut self, git: impl Into<String>) -> Self {
+        self.inner.git = Some(git.into());
+        self
+    }
+
+    /// Branch to use when installing from git
+    pub fn branch(mut self, branch: impl Into<String>) -> Self {
+        self.inner.branch = Some(branch.into());
+        self
+    }
+
+    /// Tag to use when installing from git
+    pub fn tag(mut self, tag: impl Into<String>) -> Self {
+        self.inner.tag = Some(tag.into());
+        self
+    }
+
+    /// Specific commit to use when installing from git
+    pub fn rev(mut self, rev: impl Into<String>) -> Self {
+        self.inner.rev = Some(rev.into());
+        self
+    }
+
+    /// Filesystem path to local crate to install
+    pub fn path(mut self, path: impl Into<path::PathBuf>) -> Self {
+        self.inner.path = Some(path.into());
+        self
+    }
+
+    /// Specify a version to install
+    pub fn version(mut self, version: impl Into<String>) -> Self {
+        self.inner.version = Some(version.into());
+        self
+    }
+
+    /// Directory to install packages into
+    pub fn root(mut self, root: impl Into<path::PathBuf>) -> Self {
+        self.inner.root = Some(root.into());
+        self
+    }
+
+    /// Finish building the flags
+    pub fn build(self) -> Install {
+        self.inner
+    }
+}
+
+impl From<InstallBuilder> for Install {
+    fn from(builder: InstallBuilder) -> Self {
+        builder.build()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use clap::StructOpt;
+
+    #[derive(Debug, StructOpt)]
+    struct Args {
+        #[clap(flatten)]
+        install: Install,
+    }
+
+    #[test]
+    fn verify_app() {
+        use clap::CommandFactory;
+        Args::command().debug_assert()
+    }
+
+    #[test]
+    fn parse_sources() {
+        let args = Args::try_parse_from(["test"]).unwrap();
+        assert_eq!(
+            args.install.source(),
+            InstallSource::Registry { version: None }
+        );
+
+        let args = Args::try_parse_from(["test", "--vers", "1.0"]).unwrap();
+        assert_eq!(
+            args.install.source(),
+            InstallSource::Registry {
+                version: Some("1.0".to_owned())
+            }
+        );
+
+        let args = Args::try_parse_from(["test", "--git", "https://foo", "--tag", "v1"]).unwrap();
+        assert_eq!(
+            args.install.source(),
+            InstallSource::Git {
+                url: "https://foo".to_owned(),
+                reference: GitReference::Tag("v1".to_owned()),
+                version: None,
+            }
+        );
+
+        let args = Args::try_parse_from(["test", "--path", "crates/foo"]).unwrap();
+        assert_eq!(
+            args.install.source(),
+            InstallSource::Path {
+                path: path::PathBuf::from("crates/foo"),
+                version: None,
+            }
+        );
+    }
+
+    #[test]
+    fn parse_conflicts() {
+        assert!(Args::try_parse_from(["test", "--branch", "main"]).is_err());
+        assert!(Args::try_parse_from(["test", "--path", ".", "--git", "https://foo"]).is_err());
+        assert!(Args::try_parse_from([
+            "test",
+            "--git",
+            "https://foo",
+            "--branch",
+            "main",
+            "--rev",
+            "abc"
+        ])
+        .is_err());
+    }
+
+    #[cfg(feature = "config")]
+    #[test]
+    fn root() {
+        let mut config = crate::CargoConfig::load_from(
+            path::Path::new("tests/fixtures/config/nested"),
+            path::Path::new("tests/fixtures/config/home"),
+        )
+        .unwrap();
+
+        let install = Install::default();
+        assert_eq!(
+            install.root(&config).unwrap(),
+            path::PathBuf::from("tests/fixtures/config/installed")
+        );
+
+        config.set_env(vec![("CARGO_INSTALL_ROOT", "from-env")]);
+        assert_eq!(
+            install.root(&config).unwrap(),
+            path::PathBuf::from("tests/fixtures/config/nested/from-env")
+        );
+
+        let install = Install {
+            root: Some(path::PathBuf::from("/from-cli")),
+            ..Default::default()
+        };
+        assert_eq!(
+            install.root(&config).unwrap(),
+            path::PathBuf::from("/from-cli")
+        );
+    }
+
+    #[cfg(feature = "config")]
+    #[test]
+    fn root_defaults_to_cargo_home() {
+        let config = crate::CargoConfig::load_from(
+            path::Path::new("tests/fixtures/simple"),
+            path::Path::new("tests/fixtures/config/home"),
+        )
+        .unwrap();
+        assert_eq!(
+            Install::default().root(&config).unwrap(),
+            path::PathBuf::from("tests/fixtures/config/home")
+        );
+    }
+
+    #[test]
+    fn args_with_prefix() {
+        let args = InstallArgs::with_prefix("tool-");
+        let cmd = args.augment_args(clap::Command::new("test"));
+        cmd.clone().debug_assert();
+
+        assert!(cmd
+            .clone()
+            .try_get_matches_from(["test", "--tool-branch", "main"])
+            .is_err());
+
+        let matches = cmd
+            .try_get_matches_from([
+                "test",
+                "--tool-git",
+                "https://example.com/repo",
+                "--tool-tag",
+                "v1",
+                "--tool-vers",
+                "1.0",
+            ])
+            .unwrap();
+        let install = args.from_arg_matches(&matches);
+        assert_eq!(
+            install.source(),
+            InstallSource::Git {
+                url: "https://example.com/repo".to_owned(),
+                reference: GitReference::Tag("v1".to_owned()),
+                version: Some("1.0".to_owned()),
+            }
+        );
+    }
+}
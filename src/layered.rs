@@ -0,0 +1,248 @@
+//! Plugin defaults from `[package.metadata.<tool>]` and `[workspace.metadata.<tool>]`.
+
+use crate::Features;
+use crate::Workspace;
+
+/// Flags layered from the command-line and `Cargo.toml` metadata tables.
+///
+/// Keys match the command-line flags, for example:
+/// ```toml
+/// [workspace.metadata.mytool]
+/// exclude = ["xtask"]
+/// features = ["serde"]
+/// ```
+///
+/// Precedence is the command-line, then `[package.metadata.<tool>]`, then
+/// `[workspace.metadata.<tool>]`; see [`Workspace::with_defaults`] and [`Features::with_defaults`]
+/// for how each flag is combined.  Unrecognized keys are ignored so the table may hold other
+/// tool settings.
+///
+/// Note: Requires the features `cargo_metadata` and `serde`.
+#[derive(Default, Clone, Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "kebab-case", default)]
+#[non_exhaustive]
+pub struct LayeredOptions {
+    #[serde(flatten)]
+    pub workspace: Workspace,
+    #[serde(flatten)]
+    pub features: Features,
+}
+
+impl LayeredOptions {
+    /// Options from the command-line.
+    pub fn from_cli(workspace: Workspace, features: Features) -> Self {
+        Self {
+            workspace,
+            features,
+        }
+    }
+
+    /// Defaults from `[workspace.metadata.<tool>]`.
+    pub fn from_workspace_metadata(
+        meta: &cargo_metadata::Metadata,
+        tool: &str,
+    ) -> Result<Self, cargo_metadata::Error> {
+        match meta.workspace_metadata.get(tool) {
+            Some(table) => {
+                serde::Deserialize::deserialize(table).map_err(cargo_metadata::Error::Json)
+            }
+            None => Ok(Self::default()),
+        }
+    }
+
+    /// Defaults from `[package.metadata.<tool>]`.
+    pub fn from_package_metadata(
+        package: &cargo_metadata::Package,
+        tool: &str,
+    ) -> Result<Self, cargo_metadata::Error> {
+        match package.metadata.get(tool) {
+            Some(table) => {
+                serde::Deserialize::deserialize(table).map_err(cargo_metadata::Error::Json)
+            }
+            None => Ok(Self::default()),
+        }
+    }
+
+    /// Fill in anything not set in `self` from `defaults`.
+    pub fn with_defaults(self, defaults: &LayeredOptions) -> Self {
+        Self {
+            workspace: self.workspace.with_defaults(&defaults.workspace),
+            features: self.features.with_defaults(&defaults.features),
+        }
+    }
+
+    /// Layer `self` over the workspace's defaults for `tool`.
+    pub fn with_workspace_defaults(
+        self,
+        meta: &cargo_metadata::Metadata,
+        tool: &str,
+    ) -> Result<Self, cargo_metadata::Error> {
+        let defaults = Self::from_workspace_metadata(meta, tool)?;
+        Ok(self.with_defaults(&defaults))
+    }
+
+    /// Layer `self` over `package`'s defaults for `tool`, and then the workspace's.
+    pub fn with_package_defaults(
+        self,
+        meta: &cargo_metadata::Metadata,
+        package: &cargo_metadata::Package,
+        tool: &str,
+    ) -> Result<Self, cargo_metadata::Error> {
+        let package_defaults = Self::from_package_metadata(package, tool)?;
+        let workspace_defaults = Self::from_workspace_metadata(meta, tool)?;
+        Ok(self
+            .with_defaults(&package_defaults)
+            .with_defaults(&workspace_defaults))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn metadata() -> cargo_metadata::Metadata {
+        let mut metadata = cargo_metadata::MetadataCommand::new();
+        metadata.manifest_path("tests/fixtures/tool_metadata/Cargo.toml");
+        metadata.exec().unwrap()
+    }
+
+    fn package<'m>(meta: &'m cargo_metadata::Metadata, name: &str) -> &'m cargo_metadata::Package {
+        meta.packages.iter().find(|p| p.name == name).unwrap()
+    }
+
+    #[test]
+    fn missing_tool() {
+        let meta = metadata();
+        let options = LayeredOptions::from_workspace_metadata(&meta, "othertool").unwrap();
+        assert_eq!(options, LayeredOptions::default());
+    }
+
+    #[test]
+    fn workspace_defaults() {
+        let meta = metadata();
+        let options = LayeredOptions::from_cli(Workspace::default(), Features::default())
+            .with_workspace_defaults(&meta, "mytool")
+            .unwrap();
+        assert_eq!(options.workspace.exclude, vec!["b".to_owned()]);
+        assert_eq!(options.features.features, vec!["ws".to_owned()]);
+
+        let (included, excluded) = options.workspace.partition_packages(&meta);
+        assert_eq!(included.len(), 1);
+        assert_eq!(excluded.len(), 1);
+    }
+
+    #[test]
+    fn cli_packages_drop_default_excludes() {
+        let meta = metadata();
+        let cli = Workspace {
+            package: vec!["b".to_owned()],
+            ..Default::default()
+        };
+        let options = LayeredOptions::from_cli(cli, Features::default())
+            .with_workspace_defaults(&meta, "mytool")
+            .unwrap();
+        assert_eq!(options.workspace.package, vec!["b".to_owned()]);
+        assert!(options.workspace.exclude.is_empty());
+    }
+
+    #[test]
+    fn cli_packages_drop_default_workspace() {
+        let meta = metadata();
+        let cli = Workspace {
+            package: vec!["b".to_owned()],
+            ..Default::default()
+        };
+        let defaults = Workspace {
+            workspace: true,
+            all: true,
+            exclude: vec!["a".to_owned()],
+            ..Default::default()
+        };
+        let workspace = cli.with_defaults(&defaults);
+        assert!(!workspace.workspace);
+        assert!(!workspace.all);
+        assert!(workspace.exclude.is_empty());
+        let (included, _) = workspace.partition_packages(&meta);
+        let included: Vec<_> = included.iter().map(|p| p.name.as_str()).collect();
+        assert_eq!(included, ["b"]);
+    }
+
+    #[test]
+    fn cli_exclude_drops_default_packages() {
+        let meta = metadata();
+        let cli = Workspace {
+            exclude: vec!["a".to_owned()],
+            ..Default::default()
+        };
+        let defaults = Workspace {
+            package: vec!["a".to_owned()],
+            ..Default::default()
+        };
+        let workspace = cli.with_defaults(&defaults);
+        assert!(workspace.package.is_empty());
+        let (included, _) = workspace.partition_packages(&meta);
+        let included: Vec<_> = included.iter().map(|p| p.name.as_str()).collect();
+        assert_eq!(included, ["b"]);
+    }
+
+    #[test]
+    fn default_flags_stay_enabled() {
+        let features = Features::default().with_defaults(&Features {
+            no_default_features: true,
+            ..Default::default()
+        });
+        assert!(features.no_default_features);
+        assert!(!features.all_features);
+
+        let workspace = Workspace {
+            package: vec!["b".to_owned()],
+            ..Default::default()
+        }
+        .with_defaults(&Workspace {
+            with_dependents: true,
+            ..Default::default()
+        });
+        assert!(workspace.with_dependents);
+        assert!(!workspace.with_dependencies);
+    }
+
+    #[test]
+    fn cli_excludes_combine() {
+        let meta = metadata();
+        let cli = Workspace {
+            exclude: vec!["a".to_owned()],
+            ..Default::default()
+        };
+        let options = LayeredOptions::from_cli(cli, Features::default())
+            .with_workspace_defaults(&meta, "mytool")
+            .unwrap();
+        assert_eq!(
+            options.workspace.exclude,
+            vec!["a".to_owned(), "b".to_owned()]
+        );
+    }
+
+    #[test]
+    fn package_defaults() {
+        let meta = metadata();
+        let cli = Features {
+            features: vec!["cli".to_owned()],
+            ..Default::default()
+        };
+        let options = LayeredOptions::from_cli(Workspace::default(), cli)
+            .with_package_defaults(&meta, package(&meta, "a"), "mytool")
+            .unwrap();
+        assert_eq!(
+            options.features.features,
+            vec!["cli".to_owned(), "pkg".to_owned(), "ws".to_owned()]
+        );
+        assert!(options.features.no_default_features);
+    }
+
+    #[test]
+    fn unknown_keys_ignored() {
+        let meta = metadata();
+        let options = LayeredOptions::from_package_metadata(package(&meta, "b"), "mytool").unwrap();
+        assert_eq!(options, LayeredOptions::default());
+    }
+}
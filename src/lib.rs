@@ -27,7 +27,7 @@
 //! ## Relevant crates
 //!
 //! Other crates that might be useful for cargo plugins:
-//! * [escargot][escargot] for wrapping `cargo-build`, `carg-run`, `cargo-test`, etc.
+//! * [escargot][escargot] for wrapping `cargo-build`, `carg-run`, `cargo-test`, etc. Enable the `escargot` feature to forward these flags to it.
 //! * [cargo_metadata][cargo_metadata] for getting crate information.
 //! * [clap-verbosity][clap-verbosity] for adding logging to your CLI.
 //!
@@ -38,12 +38,69 @@
 #![warn(missing_debug_implementations)]
 #![warn(unused_extern_crates)]
 
+mod args;
+mod backend;
+mod binary;
+#[cfg(feature = "cargo_metadata")]
+mod cache;
+mod cfg;
+#[cfg(feature = "cargo_metadata")]
+mod changed;
+mod compilation;
+#[cfg(feature = "config")]
+mod config;
+#[cfg(feature = "cargo_metadata")]
+mod discover;
+mod edges;
+mod error;
+#[cfg(feature = "cargo_metadata")]
+mod executor;
 mod features;
+mod graph;
+mod install;
+#[cfg(all(feature = "cargo_metadata", feature = "serde"))]
+mod layered;
 mod manifest;
+#[cfg(feature = "cargo_metadata")]
+mod order;
+mod registry;
+mod selection;
+mod shell;
+#[cfg(feature = "cargo_metadata")]
+mod targets;
+#[cfg(test)]
+mod test_support;
+#[cfg(any(feature = "testing", all(test, feature = "cargo_metadata")))]
+pub mod testing;
 mod workspace;
 
+pub use backend::*;
+pub use binary::*;
+#[cfg(feature = "cargo_metadata")]
+pub use cache::*;
+pub use cfg::*;
+pub use compilation::*;
+#[cfg(feature = "config")]
+pub use config::*;
+#[cfg(feature = "cargo_metadata")]
+pub use discover::*;
+pub use edges::*;
+pub use error::*;
+#[cfg(feature = "cargo_metadata")]
+pub use executor::*;
 pub use features::*;
+pub use graph::*;
+pub use install::*;
+#[cfg(all(feature = "cargo_metadata", feature = "serde"))]
+pub use layered::*;
 pub use manifest::*;
+#[cfg(feature = "cargo_metadata")]
+pub use order::*;
+pub use registry::*;
+pub use selection::*;
+pub use shell::*;
+#[cfg(feature = "cargo_metadata")]
+pub use targets::*;
 pub use workspace::*;
 
 #[macro_use]
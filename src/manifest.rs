@@ -2,26 +2,310 @@
 
 use std::path;
 
-#[derive(Default, Clone, Debug, PartialEq, Eq, clap::Args)]
+#[derive(Default, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "kebab-case", default))]
 #[non_exhaustive]
 pub struct Manifest {
-    #[clap(long, name = "PATH", parse(from_os_str))]
     /// Path to Cargo.toml
     pub manifest_path: Option<path::PathBuf>,
+    /// Path to Cargo.toml of another workspace to process, may be repeated
+    pub extra_manifest_path: Vec<path::PathBuf>,
+    /// Process every workspace found under the current directory
+    pub discover: bool,
+    /// Read `cargo metadata --format-version 1` output from PATH instead of running cargo (`-` for stdin)
+    pub metadata_file: Option<path::PathBuf>,
+}
+
+impl From<path::PathBuf> for Manifest {
+    fn from(manifest_path: path::PathBuf) -> Self {
+        Self {
+            manifest_path: Some(manifest_path),
+            ..Default::default()
+        }
+    }
+}
+
+impl From<&path::Path> for Manifest {
+    fn from(manifest_path: &path::Path) -> Self {
+        manifest_path.to_owned().into()
+    }
+}
+
+impl Manifest {
+    /// What `--manifest-path` points to, see [`ManifestPath::resolve`].
+    pub fn resolve_manifest_path(&self) -> Option<ManifestPath> {
+        self.manifest_path
+            .as_ref()
+            .map(|manifest_path| ManifestPath::resolve(manifest_path))
+    }
+
+    /// What `--manifest-path` and each `--extra-manifest-path` point to, see
+    /// [`ManifestPath::resolve`].
+    ///
+    /// Only [`Manifest::workspaces`] processes `--extra-manifest-path`; commands for a single
+    /// workspace, like [`Manifest::metadata`], use [`Manifest::resolve_manifest_path`].
+    pub fn resolve_manifest_paths(&self) -> Vec<ManifestPath> {
+        self.manifest_path
+            .iter()
+            .chain(self.extra_manifest_path.iter())
+            .map(|manifest_path| ManifestPath::resolve(manifest_path))
+            .collect()
+    }
+}
+
+/// A resolved `--manifest-path`, see [`Manifest::resolve_manifest_path`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ManifestPath {
+    /// A `Cargo.toml`
+    Toml(path::PathBuf),
+    /// A single-file package, requiring `-Zscript`
+    Script(path::PathBuf),
+}
+
+impl ManifestPath {
+    /// What `path` points to
+    ///
+    /// A directory is resolved to the `Cargo.toml` within it, a `.rs` file is a single-file
+    /// package with an embedded manifest.
+    pub fn resolve(path: &path::Path) -> Self {
+        if path.is_dir() {
+            ManifestPath::Toml(path.join("Cargo.toml"))
+        } else if path.extension().map_or(false, |ext| ext == "rs") {
+            ManifestPath::Script(path.to_owned())
+        } else {
+            ManifestPath::Toml(path.to_owned())
+        }
+    }
+
+    /// Path to pass to `cargo --manifest-path`
+    pub fn path(&self) -> &path::Path {
+        match self {
+            ManifestPath::Toml(path) | ManifestPath::Script(path) => path,
+        }
+    }
+
+    /// Whether this is a single-file package
+    pub fn is_script(&self) -> bool {
+        matches!(self, ManifestPath::Script(_))
+    }
 }
 
 #[cfg(feature = "cargo_metadata")]
 impl Manifest {
-    /// Create a `cargo_metadata::MetadataCommand`
+    /// Create a `cargo_metadata::MetadataCommand` running [`CargoBinary::from_env`][crate::CargoBinary::from_env]
+    ///
+    /// This ignores `--metadata-file`, see [`Manifest::exec_metadata`].
     ///
     /// Note: Requires the features `cargo_metadata`.
     pub fn metadata(&self) -> cargo_metadata::MetadataCommand {
-        let mut c = cargo_metadata::MetadataCommand::new();
-        if let Some(ref manifest_path) = self.manifest_path {
-            c.manifest_path(manifest_path);
+        self.metadata_with(&crate::CargoBinary::from_env())
+    }
+
+    /// Create a `cargo_metadata::MetadataCommand` running `cargo`
+    ///
+    /// A single-file package is passed with `-Zscript` through `MetadataCommand::other_options`,
+    /// so it requires a nightly toolchain.
+    ///
+    /// Note: Requires the features `cargo_metadata`.
+    pub fn metadata_with(&self, cargo: &crate::CargoBinary) -> cargo_metadata::MetadataCommand {
+        let mut c = cargo.metadata();
+        if let Some(manifest_path) = self.resolve_manifest_path() {
+            c.manifest_path(manifest_path.path());
+            if manifest_path.is_script() {
+                c.other_options(vec!["-Zscript".to_owned()]);
+            }
         }
         c
     }
+
+    /// Run `cmd`, or read its output from `--metadata-file` when given.
+    ///
+    /// A metadata file is used as-is, so flags forwarded to `cmd` (like [`Features`][crate::Features])
+    /// only take effect if the file was generated with them.
+    ///
+    /// ```rust,no_run
+    /// let manifest = clap_cargo::Manifest::default();
+    /// let features = clap_cargo::Features::default();
+    /// let mut cmd = manifest.metadata();
+    /// features.forward_metadata(&mut cmd);
+    /// let meta = manifest.exec_metadata(&cmd).unwrap();
+    /// ```
+    ///
+    /// Note: Requires the features `cargo_metadata`.
+    pub fn exec_metadata(
+        &self,
+        cmd: &cargo_metadata::MetadataCommand,
+    ) -> Result<cargo_metadata::Metadata, crate::Error> {
+        self.exec_metadata_with(&crate::CargoBinary::from_env(), cmd)
+    }
+
+    /// Like [`Manifest::exec_metadata`], running `cmd` with [`CargoBinary::exec_metadata`][crate::CargoBinary::exec_metadata]
+    /// so `cargo`'s toolchain also provides `rustc`.
+    ///
+    /// Note: Requires the features `cargo_metadata`.
+    pub fn exec_metadata_with(
+        &self,
+        cargo: &crate::CargoBinary,
+        cmd: &cargo_metadata::MetadataCommand,
+    ) -> Result<cargo_metadata::Metadata, crate::Error> {
+        match self.metadata_file {
+            Some(ref metadata_file) => read_metadata_file(metadata_file),
+            None => Ok(cargo.exec_metadata(cmd)?),
+        }
+    }
+}
+
+/// The `cargo metadata --format-version` that `cargo_metadata` reads
+#[cfg(feature = "cargo_metadata")]
+const METADATA_FORMAT_VERSION: u64 = 1;
+
+#[cfg(feature = "cargo_metadata")]
+fn read_metadata_file(path: &path::Path) -> Result<cargo_metadata::Metadata, crate::Error> {
+    let json = if path == path::Path::new("-") {
+        let mut json = String::new();
+        std::io::Read::read_to_string(&mut std::io::stdin(), &mut json)?;
+        json
+    } else {
+        std::fs::read_to_string(path)?
+    };
+    let json: serde_json::Value =
+        serde_json::from_str(&json).map_err(cargo_metadata::Error::Json)?;
+    let found = json.get("version").and_then(serde_json::Value::as_u64);
+    if found != Some(METADATA_FORMAT_VERSION) {
+        return Err(crate::Error::MetadataFormat {
+            path: path.to_owned(),
+            found,
+            supported: METADATA_FORMAT_VERSION,
+        });
+    }
+    Ok(serde_json::from_value(json).map_err(cargo_metadata::Error::Json)?)
+}
+
+#[cfg(feature = "escargot")]
+impl Manifest {
+    /// Forward these flags to an `escargot::CargoBuild`.
+    ///
+    /// Note: Requires the features `escargot`.
+    pub fn forward_escargot(&self, mut cmd: escargot::CargoBuild) -> escargot::CargoBuild {
+        if let Some(manifest_path) = self.resolve_manifest_path() {
+            cmd = cmd.manifest_path(manifest_path.path());
+            if manifest_path.is_script() {
+                cmd = cmd.arg("-Zscript");
+            }
+        }
+        cmd
+    }
+}
+
+crate::args::flag_group_args!(ManifestArgs for Manifest {
+    "manifest-path" => "PATH",
+    "extra-manifest-path",
+    "discover",
+    "metadata-file",
+});
+
+impl ManifestArgs {
+    /// Register the flags on `cmd`.
+    pub fn augment_args<'help>(&'help self, cmd: clap::Command<'help>) -> clap::Command<'help> {
+        let names = &self.names;
+        cmd.arg(
+            names
+                .arg("manifest-path")
+                .value_name("PATH")
+                .allow_invalid_utf8(true)
+                .help("Path to Cargo.toml"),
+        )
+        .arg(
+            names
+                .arg("extra-manifest-path")
+                .value_name("PATH")
+                .multiple_occurrences(true)
+                .allow_invalid_utf8(true)
+                .help("Path to Cargo.toml of another workspace to process, may be repeated"),
+        )
+        .arg(
+            names
+                .arg("discover")
+                .help("Process every workspace found under the current directory"),
+        )
+        .arg(
+            names
+                .arg("metadata-file")
+                .value_name("PATH")
+                .allow_invalid_utf8(true)
+                .help("Read `cargo metadata --format-version 1` output from PATH instead of running cargo (`-` for stdin)"),
+        )
+    }
+
+    /// Read the flags registered by [`ManifestArgs::augment_args`] from `matches`.
+    pub fn from_arg_matches(&self, matches: &clap::ArgMatches) -> Manifest {
+        let names = &self.names;
+        Manifest {
+            manifest_path: names.path(matches, "manifest-path"),
+            extra_manifest_path: names.paths(matches, "extra-manifest-path"),
+            discover: names.flag(matches, "discover"),
+            metadata_file: names.path(matches, "metadata-file"),
+        }
+    }
+}
+
+impl Manifest {
+    /// Build [`Manifest`] flags programmatically.
+    ///
+    /// ```rust
+    /// let manifest = clap_cargo::Manifest::builder()
+    ///     .manifest_path("crates/foo/Cargo.toml")
+    ///     .build();
+    /// assert!(manifest.manifest_path.is_some());
+    /// ```
+    pub fn builder() -> ManifestBuilder {
+        ManifestBuilder::default()
+    }
+}
+
+/// Builder for [`Manifest`], see [`Manifest::builder`].
+#[derive(Default, Clone, Debug, PartialEq, Eq)]
+pub struct ManifestBuilder {
+    inner: Manifest,
+}
+
+impl ManifestBuilder {
+    /// Path to Cargo.toml
+    pub fn manifest_path(mut self, manifest_path: impl Into<path::PathBuf>) -> Self {
+        self.inner.manifest_path = Some(manifest_path.into());
+        self
+    }
+
+    /// Path to Cargo.toml of another workspace to process, in addition to earlier ones
+    pub fn extra_manifest_path(mut self, manifest_path: impl Into<path::PathBuf>) -> Self {
+        self.inner.extra_manifest_path.push(manifest_path.into());
+        self
+    }
+
+    /// Process every workspace found under the current directory
+    pub fn discover(mut self, yes: bool) -> Self {
+        self.inner.discover = yes;
+        self
+    }
+
+    /// Read `cargo metadata` output from this file (`-` for stdin)
+    pub fn metadata_file(mut self, metadata_file: impl Into<path::PathBuf>) -> Self {
+        self.inner.metadata_file = Some(metadata_file.into());
+        self
+    }
+
+    /// Finish building the flags
+    pub fn build(self) -> Manifest {
+        self.inner
+    }
+}
+
+impl From<ManifestBuilder> for Manifest {
+    fn from(builder: ManifestBuilder) -> Self {
+        builder.build()
+    }
 }
 
 #[cfg(test)]
@@ -40,26 +324,218 @@ mod test {
         Cli::command().debug_assert()
     }
 
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serde_kebab_case() {
+        let manifest: Manifest =
+            serde_json::from_str(r#"{"manifest-path": "foo/Cargo.toml", "discover": true}"#)
+                .unwrap();
+        assert_eq!(
+            manifest.manifest_path,
+            Some(path::PathBuf::from("foo/Cargo.toml"))
+        );
+        assert!(manifest.discover);
+    }
+
     #[cfg(feature = "cargo_metadata")]
     #[test]
     fn metadata_with_path() {
         let manifest = Manifest {
             manifest_path: Some(path::PathBuf::from("tests/fixtures/simple/Cargo.toml")),
+            ..Default::default()
         };
         let metadata = manifest.metadata();
         metadata.exec().unwrap();
         // TODO verify we forwarded correctly.
     }
 
+    #[test]
+    fn resolve_manifest_path() {
+        let resolve = |path: &str| Manifest::from(path::Path::new(path)).resolve_manifest_path();
+        assert_eq!(Manifest::default().resolve_manifest_path(), None);
+        assert_eq!(
+            resolve("tests/fixtures/simple"),
+            Some(ManifestPath::Toml(path::PathBuf::from(
+                "tests/fixtures/simple/Cargo.toml"
+            )))
+        );
+        assert_eq!(
+            resolve("tests/fixtures/simple/Cargo.toml"),
+            Some(ManifestPath::Toml(path::PathBuf::from(
+                "tests/fixtures/simple/Cargo.toml"
+            )))
+        );
+        let script = resolve("scripts/release.rs").unwrap();
+        assert!(script.is_script());
+        assert_eq!(script.path(), path::Path::new("scripts/release.rs"));
+    }
+
+    #[cfg(feature = "cargo_metadata")]
+    #[test]
+    fn metadata_with_directory() {
+        let manifest = Manifest::from(path::Path::new("tests/fixtures/simple"));
+        let metadata = manifest.metadata().exec().unwrap();
+        assert_eq!(metadata.packages[0].name, "simple");
+    }
+
+    #[cfg(feature = "cargo_metadata")]
+    #[test]
+    fn metadata_with_script() {
+        let manifest = Manifest::from(path::Path::new("scripts/release.rs"));
+        let cmd = format!("{:?}", manifest.metadata().cargo_command());
+        assert!(
+            cmd.contains(r#""--manifest-path" "scripts/release.rs""#),
+            "{}",
+            cmd
+        );
+        assert!(cmd.contains(r#""-Zscript""#), "{}", cmd);
+
+        let manifest = Manifest::from(path::Path::new("tests/fixtures/simple"));
+        let cmd = format!("{:?}", manifest.metadata().cargo_command());
+        assert!(!cmd.contains("-Zscript"), "{}", cmd);
+    }
+
     #[cfg(feature = "cargo_metadata")]
     #[test]
     fn metadata_without_path() {
         let cwd = path::PathBuf::from("tests/fixtures/simple");
         let manifest = Manifest {
-            manifest_path: None,
+            ..Default::default()
         };
         let mut metadata = manifest.metadata();
         metadata.current_dir(cwd).exec().unwrap();
         // TODO verify we forwarded correctly.
     }
+
+    #[cfg(feature = "cargo_metadata")]
+    fn metadata_dump(dir: &path::Path) -> path::PathBuf {
+        let output = Manifest::from(path::Path::new("tests/fixtures/mixed_ws/Cargo.toml"))
+            .metadata()
+            .cargo_command()
+            .output()
+            .unwrap();
+        assert!(output.status.success());
+        let dump = dir.join("metadata.json");
+        std::fs::write(&dump, output.stdout).unwrap();
+        dump
+    }
+
+    #[cfg(feature = "cargo_metadata")]
+    #[test]
+    fn metadata_file_bypasses_cargo() {
+        let dir = tempfile::tempdir().unwrap();
+        let manifest = Manifest {
+            manifest_path: Some(path::PathBuf::from("tests/fixtures/missing/Cargo.toml")),
+            metadata_file: Some(metadata_dump(dir.path())),
+            ..Default::default()
+        };
+        let mut cmd = manifest.metadata();
+        crate::Features {
+            all_features: true,
+            ..Default::default()
+        }
+        .forward_metadata(&mut cmd);
+        let meta = manifest.exec_metadata(&cmd).unwrap();
+
+        let (included, _) = crate::Workspace::default().partition_packages(&meta);
+        assert_eq!(included.len(), 1);
+        assert_eq!(included[0].name, "b");
+    }
+
+    #[cfg(feature = "cargo_metadata")]
+    #[test]
+    fn metadata_file_rejects_other_versions() {
+        let dir = tempfile::tempdir().unwrap();
+        let dump = metadata_dump(dir.path());
+        let json = std::fs::read_to_string(&dump).unwrap();
+        std::fs::write(&dump, json.replace("\"version\":1,", "\"version\":2,")).unwrap();
+        let manifest = Manifest::builder().metadata_file(&dump).build();
+        let err = manifest.exec_metadata(&manifest.metadata()).unwrap_err();
+        assert!(
+            matches!(
+                err,
+                crate::Error::MetadataFormat {
+                    found: Some(2),
+                    supported: 1,
+                    ..
+                }
+            ),
+            "{:?}",
+            err
+        );
+
+        std::fs::write(&dump, "{}").unwrap();
+        let err = manifest.exec_metadata(&manifest.metadata()).unwrap_err();
+        assert!(
+            matches!(err, crate::Error::MetadataFormat { found: None, .. }),
+            "{:?}",
+            err
+        );
+    }
+
+    #[cfg(feature = "escargot")]
+    #[test]
+    fn forward_escargot() {
+        let manifest = Manifest {
+            manifest_path: Some(path::PathBuf::from("tests/fixtures/simple/Cargo.toml")),
+            ..Default::default()
+        };
+        crate::test_support::build(manifest.forward_escargot(escargot::CargoBuild::new())).unwrap();
+
+        let manifest = Manifest {
+            manifest_path: Some(path::PathBuf::from("tests/fixtures/missing/Cargo.toml")),
+            ..Default::default()
+        };
+        crate::test_support::build(manifest.forward_escargot(escargot::CargoBuild::new()))
+            .unwrap_err();
+    }
+
+    #[test]
+    fn args_with_prefix() {
+        let args = ManifestArgs::with_prefix("base-");
+        let cmd = args.augment_args(clap::Command::new("test").arg(clap::Arg::new("PATH")));
+        cmd.clone().debug_assert();
+
+        let matches = cmd
+            .try_get_matches_from([
+                "test",
+                "--base-manifest-path",
+                "foo/Cargo.toml",
+                "--base-extra-manifest-path",
+                "baz",
+                "--base-extra-manifest-path",
+                "qux",
+                "bar",
+            ])
+            .unwrap();
+        let manifest = args.from_arg_matches(&matches);
+        assert_eq!(
+            manifest.manifest_path,
+            Some(path::PathBuf::from("foo/Cargo.toml"))
+        );
+        assert_eq!(
+            manifest.extra_manifest_path,
+            [path::PathBuf::from("baz"), path::PathBuf::from("qux")]
+        );
+        assert_eq!(matches.value_of("PATH"), Some("bar"));
+
+        args.augment_args(clap::Command::new("test"))
+            .try_get_matches_from([
+                "test",
+                "--base-manifest-path",
+                "foo",
+                "--base-manifest-path",
+                "bar",
+            ])
+            .unwrap_err();
+
+        let matches = args
+            .augment_args(clap::Command::new("test"))
+            .try_get_matches_from(["test", "--base-metadata-file", "-"])
+            .unwrap();
+        assert_eq!(
+            args.from_arg_matches(&matches).metadata_file,
+            Some(path::PathBuf::from("-"))
+        );
+    }
 }
@@ -0,0 +1,314 @@
+//! Ordering workspace members by their dependencies on each other.
+
+use std::collections;
+
+/// Dependencies considered by [`topological_levels`].
+///
+/// Note: Requires the feature `cargo_metadata`.
+#[derive(Default, Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct OrderOptions {
+    /// Order by dev-dependencies, in addition to normal and build dependencies
+    pub dev_dependencies: bool,
+}
+
+/// Workspace members depend on each other in a cycle.
+///
+/// Cargo only allows these through dev-dependencies.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct CycleError {
+    /// Names of the packages in the cycle, starting and ending with the same package
+    pub cycle: Vec<String>,
+}
+
+impl std::fmt::Display for CycleError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "cyclic dependency between workspace members: {}",
+            self.cycle.join(" -> ")
+        )
+    }
+}
+
+impl std::error::Error for CycleError {}
+
+/// Sort `packages` so each comes after the workspace members it depends on.
+///
+/// See [`topological_levels`].
+///
+/// Notes:
+/// - Requires the feature `cargo_metadata`.
+/// - Without dependency information (`MetadataCommand::no_deps`), packages have no
+///   dependencies to order by; check for it with [`Workspace::validate`][crate::Workspace::validate].
+pub fn topological_order<'m>(
+    meta: &'m cargo_metadata::Metadata,
+    packages: &[&'m cargo_metadata::Package],
+    options: &OrderOptions,
+) -> Result<Vec<&'m cargo_metadata::Package>, CycleError> {
+    let levels = topological_levels(meta, packages, options)?;
+    Ok(levels.into_iter().flatten().collect())
+}
+
+/// Group `packages` into levels where each package only depends on packages in earlier levels.
+///
+/// Packages within a level do not depend on each other and can be processed in parallel; they
+/// are sorted by name.  Dependencies through workspace members that are not in `packages` are
+/// still respected.  Packages that are not workspace members are ignored.
+///
+/// Notes:
+/// - Requires the feature `cargo_metadata`.
+/// - Without dependency information (`MetadataCommand::no_deps`), packages have no
+///   dependencies to order by; check for it with [`Workspace::validate`][crate::Workspace::validate].
+pub fn topological_levels<'m>(
+    meta: &'m cargo_metadata::Metadata,
+    packages: &[&'m cargo_metadata::Package],
+    options: &OrderOptions,
+) -> Result<Vec<Vec<&'m cargo_metadata::Package>>, CycleError> {
+    let (dependencies, sorted) = sorted_members(meta, options)?;
+
+    // Only selected packages occupy a level; unselected members pass their level through
+    let selected: collections::HashSet<_> = packages.iter().map(|p| &p.id).collect();
+    let mut level = collections::HashMap::new();
+    let mut after = collections::HashMap::new();
+    for id in sorted {
+        let current = dependencies[id]
+            .iter()
+            .map(|dep| after[dep])
+            .max()
+            .unwrap_or(0);
+        level.insert(id, current);
+        after.insert(id, current + usize::from(selected.contains(id)));
+    }
+
+    let mut levels: Vec<Vec<&cargo_metadata::Package>> = Vec::new();
+    for package in packages {
+        if let Some(&current) = level.get(&package.id) {
+            if levels.len() <= current {
+                levels.resize_with(current + 1, Vec::new);
+            }
+            levels[current].push(package);
+        }
+    }
+    for packages in &mut levels {
+        packages.sort_by(|a, b| a.name.cmp(&b.name));
+        packages.dedup_by(|a, b| a.id == b.id);
+    }
+    Ok(levels)
+}
+
+/// For each of `packages`, the other `packages` it must come after.
+///
+/// Like [`topological_levels`], this sees through workspace members that are not in `packages`.
+pub(crate) fn prerequisites<'m>(
+    meta: &'m cargo_metadata::Metadata,
+    packages: &[&'m cargo_metadata::Package],
+    options: &OrderOptions,
+) -> Result<
+    collections::HashMap<
+        &'m cargo_metadata::PackageId,
+        collections::HashSet<&'m cargo_metadata::PackageId>,
+    >,
+    CycleError,
+> {
+    let (dependencies, sorted) = sorted_members(meta, options)?;
+    let selected: collections::HashSet<_> = packages.iter().map(|p| &p.id).collect();
+
+    // What a dependent of each member must wait for
+    let mut waits_on: collections::HashMap<_, collections::HashSet<_>> =
+        collections::HashMap::new();
+    let mut prerequisites = collections::HashMap::new();
+    for id in sorted {
+        let mut current = collections::HashSet::new();
+        for dep in &dependencies[id] {
+            current.extend(waits_on[dep].iter().copied());
+        }
+        if selected.contains(id) {
+            prerequisites.insert(id, current);
+            current = std::iter::once(id).collect();
+        }
+        waits_on.insert(id, current);
+    }
+    Ok(prerequisites)
+}
+
+type Dependencies<'m> =
+    collections::HashMap<&'m cargo_metadata::PackageId, Vec<&'m cargo_metadata::PackageId>>;
+
+/// Workspace members and their dependencies on each other, in topological order
+fn sorted_members<'m>(
+    meta: &'m cargo_metadata::Metadata,
+    options: &OrderOptions,
+) -> Result<(Dependencies<'m>, Vec<&'m cargo_metadata::PackageId>), CycleError> {
+    let mut kinds = vec![crate::EdgeKind::Normal, crate::EdgeKind::Build];
+    if options.dev_dependencies {
+        kinds.push(crate::EdgeKind::Development);
+    }
+
+    let graph = crate::DependencyGraph::new(meta);
+    let members: collections::HashSet<_> = meta.workspace_members.iter().collect();
+    let dependencies: Dependencies<'m> = meta
+        .workspace_members
+        .iter()
+        .map(|id| {
+            let deps = graph
+                .direct_dependencies(id, &kinds)
+                .filter(|dep| members.contains(dep) && *dep != id)
+                .collect();
+            (id, deps)
+        })
+        .collect();
+
+    let sorted = sort(&dependencies).map_err(|cycle| CycleError {
+        cycle: cycle.into_iter().map(|id| name(meta, id)).collect(),
+    })?;
+    Ok((dependencies, sorted))
+}
+
+/// Kahn's algorithm, returning a cycle on failure
+fn sort<'m>(
+    dependencies: &Dependencies<'m>,
+) -> Result<Vec<&'m cargo_metadata::PackageId>, Vec<&'m cargo_metadata::PackageId>> {
+    let mut dependents: collections::HashMap<_, Vec<_>> = collections::HashMap::new();
+    let mut remaining: collections::HashMap<_, _> = collections::HashMap::new();
+    for (id, deps) in dependencies {
+        remaining.insert(*id, deps.len());
+        for dep in deps {
+            dependents.entry(*dep).or_default().push(*id);
+        }
+    }
+
+    let mut ready: Vec<_> = remaining
+        .iter()
+        .filter(|(_, count)| **count == 0)
+        .map(|(id, _)| *id)
+        .collect();
+    let mut sorted = Vec::with_capacity(dependencies.len());
+    while let Some(id) = ready.pop() {
+        sorted.push(id);
+        for dependent in dependents.get(id).into_iter().flatten() {
+            let count = remaining
+                .get_mut(dependent)
+                .expect("all members are tracked");
+            *count -= 1;
+            if *count == 0 {
+                ready.push(dependent);
+            }
+        }
+    }
+
+    if sorted.len() == dependencies.len() {
+        Ok(sorted)
+    } else {
+        Err(find_cycle(dependencies, &remaining))
+    }
+}
+
+fn find_cycle<'m>(
+    dependencies: &Dependencies<'m>,
+    remaining: &collections::HashMap<&'m cargo_metadata::PackageId, usize>,
+) -> Vec<&'m cargo_metadata::PackageId> {
+    // Every package left over depends on another left over package, so walking them must repeat
+    let mut current = remaining
+        .iter()
+        .filter(|(_, count)| **count != 0)
+        .map(|(id, _)| *id)
+        .min()
+        .expect("a cycle exists");
+    let mut path = Vec::new();
+    loop {
+        if let Some(start) = path.iter().position(|id| *id == current) {
+            let mut cycle = path.split_off(start);
+            cycle.push(current);
+            return cycle;
+        }
+        path.push(current);
+        current = dependencies[current]
+            .iter()
+            .copied()
+            .filter(|dep| remaining.get(dep).map(|c| *c != 0).unwrap_or(false))
+            .min()
+            .expect("unsorted packages depend on other unsorted packages");
+    }
+}
+
+fn name(meta: &cargo_metadata::Metadata, id: &cargo_metadata::PackageId) -> String {
+    meta.packages
+        .iter()
+        .find(|p| p.id == *id)
+        .map(|p| p.name.clone())
+        .unwrap_or_else(|| id.repr.clone())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn metadata() -> cargo_metadata::Metadata {
+        let mut metadata = cargo_metadata::MetadataCommand::new();
+        metadata.manifest_path("tests/fixtures/graph/Cargo.toml");
+        metadata.exec().unwrap()
+    }
+
+    fn select<'m>(
+        meta: &'m cargo_metadata::Metadata,
+        names: &[&str],
+    ) -> Vec<&'m cargo_metadata::Package> {
+        meta.packages
+            .iter()
+            .filter(|p| names.contains(&p.name.as_str()))
+            .collect()
+    }
+
+    fn level_names<'m>(levels: &[Vec<&'m cargo_metadata::Package>]) -> Vec<Vec<&'m str>> {
+        levels
+            .iter()
+            .map(|level| level.iter().map(|p| p.name.as_str()).collect())
+            .collect()
+    }
+
+    #[test]
+    fn levels() {
+        let meta = metadata();
+        let packages = select(&meta, &["app", "base", "macros", "testing", "tool"]);
+        let levels = topological_levels(&meta, &packages, &OrderOptions::default()).unwrap();
+        assert_eq!(
+            level_names(&levels),
+            [vec!["base", "testing"], vec!["macros", "tool"], vec!["app"]]
+        );
+    }
+
+    #[test]
+    fn order() {
+        let meta = metadata();
+        let packages = select(&meta, &["app", "base", "macros"]);
+        let order = topological_order(&meta, &packages, &OrderOptions::default()).unwrap();
+        let names: Vec<_> = order.iter().map(|p| p.name.as_str()).collect();
+        assert_eq!(names, ["base", "macros", "app"]);
+    }
+
+    #[test]
+    fn through_unselected() {
+        let meta = metadata();
+        let packages = select(&meta, &["app", "base"]);
+        let levels = topological_levels(&meta, &packages, &OrderOptions::default()).unwrap();
+        assert_eq!(level_names(&levels), [vec!["base"], vec!["app"]]);
+    }
+
+    #[test]
+    fn dev_dependency_cycle() {
+        let meta = metadata();
+        let packages = select(&meta, &["app", "macros"]);
+        let options = OrderOptions {
+            dev_dependencies: true,
+        };
+        let err = topological_levels(&meta, &packages, &options).unwrap_err();
+        assert_eq!(err.cycle.first(), err.cycle.last());
+        let mut names = err.cycle.clone();
+        names.sort();
+        names.dedup();
+        assert_eq!(names, ["app", "macros"]);
+        assert!(err.to_string().contains("cyclic dependency"));
+    }
+}
@@ -0,0 +1,383 @@
+//! Cargo flags for selecting a registry, like `cargo publish`.
+
+/// Index used for `crates-io` when not overridden
+pub const CRATES_IO_INDEX: &str = "https://github.com/rust-lang/crates.io-index";
+
+/// Name cargo uses for the default registry
+pub const CRATES_IO_REGISTRY: &str = "crates-io";
+
+#[derive(Default, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "kebab-case", default))]
+#[non_exhaustive]
+pub struct Registry {
+    /// Registry to use
+    pub registry: Option<String>,
+    /// Registry index URL to use
+    pub index: Option<String>,
+    /// Token to use when uploading
+    pub token: Option<String>,
+    /// Perform all checks without uploading
+    pub dry_run: bool,
+    /// Allow dirty working directories to be packaged
+    pub allow_dirty: bool,
+    /// Don't verify the contents by building them
+    pub no_verify: bool,
+}
+
+/// Registry selected by [`Registry::resolve`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct RegistryTarget {
+    /// Registry name, `None` when `--index` was used
+    pub name: Option<String>,
+    /// Index URL
+    pub index: String,
+    /// Token for uploading, if one is configured
+    pub token: Option<String>,
+}
+
+#[cfg(feature = "config")]
+impl Registry {
+    /// Resolve the registry's index and credentials, like `cargo publish`.
+    ///
+    /// Order of precedence:
+    /// - `--index`, otherwise `--registry`, otherwise `registry.default`, otherwise `crates-io`
+    /// - `--token`, otherwise `registries.<name>.token` (`registry.token` for `crates-io`) from the
+    ///   environment, `$CARGO_HOME/credentials.toml` or config files.
+    ///
+    /// Note: Requires the feature `config`.
+    pub fn resolve(&self, config: &crate::CargoConfig) -> std::io::Result<RegistryTarget> {
+        if let Some(index) = self.index.as_ref() {
+            return Ok(RegistryTarget {
+                name: None,
+                index: index.clone(),
+                token: self.token.clone(),
+            });
+        }
+
+        let name = match self.registry.as_ref() {
+            Some(name) => name.clone(),
+            None => config
+                .get_string("registry.default")?
+                .map(|(name, _)| name)
+                .unwrap_or_else(|| CRATES_IO_REGISTRY.to_owned()),
+        };
+
+        let (index, token_key) = if name == CRATES_IO_REGISTRY {
+            (CRATES_IO_INDEX.to_owned(), "registry.token".to_owned())
+        } else {
+            let index = config
+                .get_string(&format!("registries.{}.index", name))?
+                .map(|(index, _)| index)
+                .ok_or_else(|| {
+                    std::io::Error::new(
+                        std::io::ErrorKind::NotFound,
+                        format!(
+                            "registry index was not found in any configuration: `{}`",
+                            name
+                        ),
+                    )
+                })?;
+            (index, format!("registries.{}.token", name))
+        };
+
+        let token = match self.token.as_ref() {
+            Some(token) => Some(token.clone()),
+            None => config.get_string(&token_key)?.map(|(token, _)| token),
+        };
+
+        Ok(RegistryTarget {
+            name: Some(name),
+            index,
+            token,
+        })
+    }
+}
+
+crate::args::flag_group_args!(RegistryArgs for Registry {
+    "registry",
+    "index",
+    "token",
+    "dry-run",
+    "allow-dirty",
+    "no-verify",
+});
+
+impl RegistryArgs {
+    /// Register the flags on `cmd`.
+    pub fn augment_args<'help>(&'help self, cmd: clap::Command<'help>) -> clap::Command<'help> {
+        let names = &self.names;
+        cmd.arg(
+            names
+                .arg("registry")
+                .value_name("REGISTRY")
+                .conflicts_with(names.get("index"))
+                .help("Registry to use"),
+        )
+        .arg(
+            names
+                .arg("index")
+                .value_name("INDEX")
+                .help("Registry index URL to use"),
+        )
+        .arg(
+            names
+                .arg("token")
+                .value_name("TOKEN")
+                .help("Token to use when uploading"),
+        )
+        .arg(
+            names
+                .arg("dry-run")
+                .help("Perform all checks without uploading"),
+        )
+        .arg(
+            names
+                .arg("allow-dirty")
+                .help("Allow dirty working directories to be packaged"),
+        )
+        .arg(
+            names
+                .arg("no-verify")
+                .help("Don't verify the contents by building them"),
+        )
+    }
+
+    /// Read the flags registered by [`RegistryArgs::augment_args`] from `matches`.
+    pub fn from_arg_matches(&self, matches: &clap::ArgMatches) -> Registry {
+        let names = &self.names;
+        Registry {
+            registry: names.value(matches, "registry"),
+            index: names.value(matches, "index"),
+            token: names.value(matches, "token"),
+            dry_run: names.flag(matches, "dry-run"),
+            allow_dirty: names.flag(matches, "allow-dirty"),
+            no_verify: names.flag(matches, "no-verify"),
+        }
+    }
+}
+
+impl Registry {
+    /// Build [`Registry`] flags programmatically.
+    ///
+    /// ```rust
+    /// let registry = clap_cargo::Registry::builder()
+    ///     .registry("internal")
+    ///     .dry_run(true)
+    ///     .build();
+    /// assert_eq!(registry.registry.as_deref(), Some("internal"));
+    /// ```
+    pub fn builder() -> RegistryBuilder {
+        RegistryBuilder::default()
+    }
+}
+
+/// Builder for [`Registry`], see [`Registry::builder`].
+#[derive(Default, Clone, Debug, PartialEq, Eq)]
+pub struct RegistryBuilder {
+    inner: Registry,
+}
+
+impl RegistryBuilder {
+    /// Registry to use
+    pub fn registry(mut self, registry: impl Into<String>) -> Self {
+        self.inner.registry = Some(registry.into());
+        self
+    }
+
+    /// Registry index URL to use
+    pub fn index(mut self, index: impl Into<String>) -> Self {
+        self.inner.index = Some(index.into());
+        self
+    }
+
+    /// Token to use when uploading
+    pub fn token(mut self, token: impl Into<String>) -> Self {
+        self.inner.token = Some(token.into());
+        self
+    }
+
+    /// Perform all checks without uploading
+    pub fn dry_run(mut self, yes: bool) -> Self {
+        self.inner.dry_run = yes;
+        self
+    }
+
+    /// Allow dirty working directories to be packaged
+    pub fn allow_dirty(mut self, yes: bool) -> Self {
+        self.inner.allow_dirty = yes;
+        self
+    }
+
+    /// Don't verify the contents by building them
+    pub fn no_verify(mut self, yes: bool) -> Self {
+        self.inner.no_verify = yes;
+        self
+    }
+
+    /// Finish building the flags
+    pub fn build(self) -> Registry {
+        self.inner
+    }
+}
+
+impl From<RegistryBuilder> for Registry {
+    fn from(builder: RegistryBuilder) -> Self {
+        builder.build()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use clap::StructOpt;
+
+    #[test]
+    fn verify_app() {
+        #[derive(Debug, clap::StructOpt)]
+        struct Cli {
+            #[clap(flatten)]
+            registry: Registry,
+        }
+
+        use clap::CommandFactory;
+        Cli::command().debug_assert()
+    }
+
+    #[test]
+    fn registry_conflicts_with_index() {
+        #[derive(Debug, StructOpt)]
+        struct Args {
+            #[clap(flatten)]
+            registry: Registry,
+        }
+
+        let res = Args::try_parse_from(["test", "--registry", "foo", "--index", "https://foo"]);
+        assert!(res.is_err());
+
+        let args = Args::try_parse_from(["test", "--registry", "foo", "--dry-run"]).unwrap();
+        assert_eq!(
+            args.registry,
+            Registry {
+                registry: Some("foo".to_owned()),
+                dry_run: true,
+                ..Default::default()
+            }
+        );
+    }
+
+    #[cfg(feature = "config")]
+    #[cfg(test)]
+    mod resolve {
+        use super::*;
+
+        fn config() -> crate::CargoConfig {
+            crate::CargoConfig::load_from(
+                std::path::Path::new("tests/fixtures/config/nested"),
+                std::path::Path::new("tests/fixtures/config/home"),
+            )
+            .unwrap()
+        }
+
+        #[test]
+        fn crates_io() {
+            let registry = Registry::default();
+            let target = registry.resolve(&config()).unwrap();
+            assert_eq!(target.name.as_deref(), Some(CRATES_IO_REGISTRY));
+            assert_eq!(target.index, CRATES_IO_INDEX);
+            assert_eq!(target.token.as_deref(), Some("crates-io-token"));
+        }
+
+        #[test]
+        fn named() {
+            let registry = Registry {
+                registry: Some("shared".to_owned()),
+                ..Default::default()
+            };
+            let target = registry.resolve(&config()).unwrap();
+            assert_eq!(target.name.as_deref(), Some("shared"));
+            assert_eq!(target.index, "https://nested.example.com/index");
+            assert_eq!(target.token.as_deref(), Some("shared-token"));
+        }
+
+        #[test]
+        fn named_without_credentials() {
+            let registry = Registry {
+                registry: Some("no-token".to_owned()),
+                ..Default::default()
+            };
+            let target = registry.resolve(&config()).unwrap();
+            assert_eq!(target.index, "https://no-token.example.com/index");
+            assert_eq!(target.token, None);
+        }
+
+        #[test]
+        fn token_flag_wins() {
+            let registry = Registry {
+                registry: Some("shared".to_owned()),
+                token: Some("cli-token".to_owned()),
+                ..Default::default()
+            };
+            let target = registry.resolve(&config()).unwrap();
+            assert_eq!(target.token.as_deref(), Some("cli-token"));
+        }
+
+        #[test]
+        fn token_from_env() {
+            let mut config = config();
+            config.set_env(vec![("CARGO_REGISTRIES_SHARED_TOKEN", "env-token")]);
+            let registry = Registry {
+                registry: Some("shared".to_owned()),
+                ..Default::default()
+            };
+            let target = registry.resolve(&config).unwrap();
+            assert_eq!(target.token.as_deref(), Some("env-token"));
+        }
+
+        #[test]
+        fn index() {
+            let registry = Registry {
+                index: Some("https://index.example.com".to_owned()),
+                ..Default::default()
+            };
+            let target = registry.resolve(&config()).unwrap();
+            assert_eq!(target.name, None);
+            assert_eq!(target.index, "https://index.example.com");
+            assert_eq!(target.token, None);
+        }
+
+        #[test]
+        fn unknown() {
+            let registry = Registry {
+                registry: Some("unknown".to_owned()),
+                ..Default::default()
+            };
+            assert!(registry.resolve(&config()).is_err());
+        }
+    }
+
+    #[test]
+    fn args_with_prefix() {
+        let args = RegistryArgs::with_prefix("upload-");
+        let cmd = args.augment_args(clap::Command::new("test"));
+        cmd.clone().debug_assert();
+
+        let res = cmd.clone().try_get_matches_from([
+            "test",
+            "--upload-registry",
+            "foo",
+            "--upload-index",
+            "https://foo",
+        ]);
+        assert!(res.is_err());
+
+        let matches = cmd
+            .try_get_matches_from(["test", "--upload-registry", "foo", "--upload-dry-run"])
+            .unwrap();
+        let registry = args.from_arg_matches(&matches);
+        assert_eq!(registry.registry.as_deref(), Some("foo"));
+        assert!(registry.dry_run);
+    }
+}
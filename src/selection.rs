@@ -0,0 +1,495 @@
+//! Selecting packages beyond `cargo`'s flags and explaining the selection.
+
+/// Flags narrowing and explaining [`Workspace`][crate::Workspace]'s selection.
+///
+/// These have no `cargo` equivalent and are only honored by [`Selection::partition_packages`],
+/// so they are a separate group for plugins to opt into.
+#[derive(Default, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "kebab-case", default))]
+#[non_exhaustive]
+pub struct Selection {
+    /// Only process packages changed since REV and the packages depending on them
+    pub since: Option<String>,
+    /// Explain why each package was or was not selected
+    pub explain_selection: bool,
+}
+
+#[cfg(feature = "cargo_metadata")]
+impl Selection {
+    /// Partition workspace members like [`Workspace::partition_packages`][crate::Workspace::partition_packages],
+    /// further limited by `--since`.
+    ///
+    /// With `--since REV`, only members with files changed since the merge-base of `REV` and
+    /// `HEAD` (including uncommitted and untracked files) are selected, along with every member
+    /// that transitively depends on them.
+    ///
+    /// Notes:
+    /// - Requires the features `cargo_metadata`.
+    /// - Fails with [`Error::NoDeps`][crate::Error::NoDeps] if `MetadataCommand::no_deps` was
+    ///   called
+    /// - Requires `git` to be installed when `--since` is used
+    pub fn partition_packages<'m>(
+        &self,
+        workspace: &crate::Workspace,
+        meta: &'m cargo_metadata::Metadata,
+    ) -> Result<
+        (
+            Vec<&'m cargo_metadata::Package>,
+            Vec<&'m cargo_metadata::Package>,
+        ),
+        crate::Error,
+    > {
+        let included: std::collections::HashSet<_> = self
+            .selection_reasons(workspace, meta)?
+            .into_iter()
+            .filter(|s| s.is_selected())
+            .map(|s| s.id)
+            .collect();
+        Ok(meta.packages.iter().partition(|p| included.contains(&p.id)))
+    }
+
+    /// Why each package is selected or not by [`Selection::partition_packages`].
+    ///
+    /// See [`render_selection`] for `--explain-selection`.
+    ///
+    /// Notes:
+    /// - Requires the features `cargo_metadata`.
+    /// - Fails with [`Error::NoDeps`][crate::Error::NoDeps] if `MetadataCommand::no_deps` was
+    ///   called
+    /// - Requires `git` to be installed when `--since` is used
+    pub fn selection_reasons<'m>(
+        &self,
+        workspace: &crate::Workspace,
+        meta: &'m cargo_metadata::Metadata,
+    ) -> Result<Vec<PackageSelection<'m, cargo_metadata::PackageId>>, crate::Error> {
+        if meta.resolve.is_none() {
+            return Err(crate::Error::NoDeps);
+        }
+        let graph = crate::MetadataGraph::new(meta);
+        let mut selections: Vec<_> = workspace
+            .selection_reasons(&graph, &crate::WalkOptions::default())
+            .into_iter()
+            .map(|selection| {
+                // Borrow from `meta` rather than `graph`
+                let package = graph
+                    .package(selection.id)
+                    .expect("package ids come from the same `Metadata`");
+                PackageSelection {
+                    id: &package.id,
+                    name: &package.name,
+                    reason: selection.reason,
+                }
+            })
+            .collect();
+        let rev = match self.since.as_deref() {
+            Some(rev) => rev,
+            None => return Ok(selections),
+        };
+
+        let changed = crate::changed::changed_packages(meta, rev)?;
+        let affected =
+            crate::DependencyGraph::new(meta).dependents(changed, &crate::WalkOptions::default());
+        for selection in &mut selections {
+            if selection.is_selected() && !affected.contains(selection.id) {
+                selection.reason = SelectionReason::Unchanged(rev.to_owned());
+            }
+        }
+        Ok(selections)
+    }
+}
+
+crate::args::flag_group_args!(SelectionArgs for Selection { "since", "explain-selection" });
+
+impl SelectionArgs {
+    /// Register the flags on `cmd`.
+    pub fn augment_args<'help>(&'help self, cmd: clap::Command<'help>) -> clap::Command<'help> {
+        let names = &self.names;
+        cmd.arg(
+            names
+                .arg("since")
+                .value_name("REV")
+                .help("Only process packages changed since REV and the packages depending on them"),
+        )
+        .arg(
+            names
+                .arg("explain-selection")
+                .help("Explain why each package was or was not selected"),
+        )
+    }
+
+    /// Read the flags registered by [`SelectionArgs::augment_args`] from `matches`.
+    pub fn from_arg_matches(&self, matches: &clap::ArgMatches) -> Selection {
+        let names = &self.names;
+        Selection {
+            since: names.value(matches, "since"),
+            explain_selection: names.flag(matches, "explain-selection"),
+        }
+    }
+}
+
+impl Selection {
+    /// Build [`Selection`] flags programmatically.
+    ///
+    /// ```rust
+    /// let selection = clap_cargo::Selection::builder().since("origin/main").build();
+    /// assert_eq!(selection.since.as_deref(), Some("origin/main"));
+    /// ```
+    pub fn builder() -> SelectionBuilder {
+        SelectionBuilder::default()
+    }
+}
+
+/// Builder for [`Selection`], see [`Selection::builder`].
+#[derive(Default, Clone, Debug, PartialEq, Eq)]
+pub struct SelectionBuilder {
+    inner: Selection,
+}
+
+impl SelectionBuilder {
+    /// Only process packages changed since a revision and the packages depending on them
+    pub fn since(mut self, since: impl Into<String>) -> Self {
+        self.inner.since = Some(since.into());
+        self
+    }
+
+    /// Explain why each package was or was not selected
+    pub fn explain_selection(mut self, yes: bool) -> Self {
+        self.inner.explain_selection = yes;
+        self
+    }
+
+    /// Finish building the flags
+    pub fn build(self) -> Selection {
+        self.inner
+    }
+}
+
+impl From<SelectionBuilder> for Selection {
+    fn from(builder: SelectionBuilder) -> Self {
+        builder.build()
+    }
+}
+
+/// Why [`Workspace`][crate::Workspace] selected or skipped a package.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum SelectionReason {
+    /// Selected as the package whose manifest was used, without any selection flags
+    Root,
+    /// Selected as a member of a virtual workspace, without any selection flags
+    ///
+    /// `cargo_metadata` does not report `default-members`, so every member is a default member.
+    DefaultMembers,
+    /// Selected by `--workspace` or `--all`
+    Workspace,
+    /// Selected by `--package SPEC`
+    Package(String),
+    /// Selected by `--with-dependents`
+    Dependent,
+    /// Selected by `--with-dependencies`
+    Dependency,
+    /// Skipped by `--exclude SPEC`
+    Excluded(String),
+    /// Skipped by `--since REV` as neither it nor its dependencies changed
+    Unchanged(String),
+    /// Skipped as no flag selected it
+    NotSelected,
+    /// Skipped as it is not a workspace member
+    NotMember,
+}
+
+impl SelectionReason {
+    /// Whether the package is processed
+    pub fn is_selected(&self) -> bool {
+        matches!(
+            self,
+            Self::Root
+                | Self::DefaultMembers
+                | Self::Workspace
+                | Self::Package(_)
+                | Self::Dependent
+                | Self::Dependency
+        )
+    }
+}
+
+impl std::fmt::Display for SelectionReason {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Root => write!(f, "root package"),
+            Self::DefaultMembers => write!(f, "default member of the workspace"),
+            Self::Workspace => write!(f, "`--workspace`"),
+            Self::Package(spec) => write!(f, "matched `--package {}`", spec),
+            Self::Dependent => write!(f, "depends on a selected package (`--with-dependents`)"),
+            Self::Dependency => write!(
+                f,
+                "depended on by a selected package (`--with-dependencies`)"
+            ),
+            Self::Excluded(spec) => write!(f, "excluded by `--exclude {}`", spec),
+            Self::Unchanged(rev) => write!(f, "unchanged since `{}`", rev),
+            Self::NotSelected => write!(f, "not selected"),
+            Self::NotMember => write!(f, "not a workspace member"),
+        }
+    }
+}
+
+/// A package and why it was or was not selected.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct PackageSelection<'g, I> {
+    pub id: &'g I,
+    pub name: &'g str,
+    pub reason: SelectionReason,
+}
+
+impl<I> PackageSelection<'_, I> {
+    /// Whether the package is processed
+    pub fn is_selected(&self) -> bool {
+        self.reason.is_selected()
+    }
+}
+
+/// Render `selections` as a table, like for `--explain-selection`.
+///
+/// Packages are listed in the order given, e.g. filter out [`SelectionReason::NotMember`] to only
+/// list workspace members.
+pub fn render_selection<I>(selections: &[PackageSelection<'_, I>]) -> String {
+    const HEADER: [&str; 3] = ["PACKAGE", "SELECTED", "REASON"];
+    let width = selections
+        .iter()
+        .map(|s| s.name.len())
+        .chain(std::iter::once(HEADER[0].len()))
+        .max()
+        .unwrap_or_default();
+
+    let mut table = format!(
+        "{:<width$}  {:<8}  {}\n",
+        HEADER[0],
+        HEADER[1],
+        HEADER[2],
+        width = width
+    );
+    for selection in selections {
+        let selected = if selection.is_selected() { "yes" } else { "no" };
+        table.push_str(&format!(
+            "{:<width$}  {:<8}  {}\n",
+            selection.name,
+            selected,
+            selection.reason,
+            width = width
+        ));
+    }
+    table
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use clap::StructOpt;
+
+    #[test]
+    fn render() {
+        let selections = [
+            PackageSelection {
+                id: &1,
+                name: "app",
+                reason: SelectionReason::Package("app".to_owned()),
+            },
+            PackageSelection {
+                id: &2,
+                name: "base-utils",
+                reason: SelectionReason::Excluded("base-utils".to_owned()),
+            },
+        ];
+        assert_eq!(
+            render_selection(&selections),
+            "\
+PACKAGE     SELECTED  REASON
+app         yes       matched `--package app`
+base-utils  no        excluded by `--exclude base-utils`
+"
+        );
+    }
+
+    #[test]
+    fn args_match_derive() {
+        let args = SelectionArgs::new();
+        let cmd = args.augment_args(clap::Command::new("test"));
+        cmd.clone().debug_assert();
+
+        let argv = ["test", "--since", "main", "--explain-selection"];
+        let matches = cmd.try_get_matches_from(argv).unwrap();
+        #[derive(Debug, StructOpt)]
+        struct Args {
+            #[clap(flatten)]
+            selection: Selection,
+        }
+        assert_eq!(
+            args.from_arg_matches(&matches),
+            Args::parse_from(argv).selection
+        );
+    }
+
+    #[test]
+    fn builder() {
+        let selection = Selection::builder()
+            .since("main")
+            .explain_selection(true)
+            .build();
+        assert_eq!(
+            selection,
+            Selection {
+                since: Some("main".to_owned()),
+                explain_selection: true,
+            }
+        );
+    }
+
+    #[cfg(feature = "cargo_metadata")]
+    mod since {
+        use super::*;
+
+        use crate::testing::{PackageSpec, TempWorkspace, WorkspaceSpec};
+        use std::fs;
+
+        fn git(workspace: &TempWorkspace, args: &[&str]) {
+            crate::test_support::git(workspace.root(), args);
+        }
+
+        fn write(workspace: &TempWorkspace, rel: &str, content: &str) {
+            fs::write(workspace.root().join(rel), content).unwrap();
+        }
+
+        /// `a` <- `b` <- `c`, and an unrelated `d`
+        fn repo() -> TempWorkspace {
+            let workspace = WorkspaceSpec::new()
+                .member(PackageSpec::new("a"))
+                .member(PackageSpec::new("b").dependency("a"))
+                .member(PackageSpec::new("c").dependency("b"))
+                .member(PackageSpec::new("d"))
+                .create()
+                .unwrap();
+            write(&workspace, ".gitignore", "target\nCargo.lock\n");
+            git(&workspace, &["init", "-q"]);
+            git(&workspace, &["add", "."]);
+            git(&workspace, &["commit", "-q", "-m", "init"]);
+            workspace
+        }
+
+        fn names(packages: &[&cargo_metadata::Package]) -> Vec<String> {
+            let mut names: Vec<_> = packages.iter().map(|p| p.name.clone()).collect();
+            names.sort();
+            names
+        }
+
+        fn partition(
+            repo: &TempWorkspace,
+            workspace: &crate::Workspace,
+            since: &str,
+        ) -> (Vec<String>, Vec<String>) {
+            let metadata = repo.metadata().unwrap();
+            let selection = Selection::builder().since(since).build();
+            let (included, excluded) = selection.partition_packages(workspace, &metadata).unwrap();
+            (names(&included), names(&excluded))
+        }
+
+        #[test]
+        fn without_since() {
+            let repo = repo();
+            let metadata = repo.metadata().unwrap();
+            let (included, excluded) = Selection::default()
+                .partition_packages(&crate::Workspace::default(), &metadata)
+                .unwrap();
+            assert_eq!(names(&included), ["a", "b", "c", "d"]);
+            assert!(excluded.is_empty());
+        }
+
+        #[test]
+        fn nothing_changed() {
+            let repo = repo();
+            let (included, excluded) = partition(&repo, &Default::default(), "HEAD");
+            assert!(included.is_empty());
+            assert_eq!(excluded, ["a", "b", "c", "d"]);
+        }
+
+        #[test]
+        fn includes_dependents() {
+            let repo = repo();
+            write(&repo, "b/src/lib.rs", "pub fn changed() {}");
+            git(&repo, &["commit", "-q", "-am", "change b"]);
+            let (included, excluded) = partition(&repo, &Default::default(), "HEAD~1");
+            assert_eq!(included, ["b", "c"]);
+            assert_eq!(excluded, ["a", "d"]);
+        }
+
+        #[test]
+        fn explain_unchanged() {
+            let repo = repo();
+            write(&repo, "b/src/lib.rs", "pub fn changed() {}");
+            git(&repo, &["commit", "-q", "-am", "change b"]);
+            let metadata = repo.metadata().unwrap();
+            let mut reasons: Vec<_> = Selection::builder()
+                .since("HEAD~1")
+                .build()
+                .selection_reasons(&Default::default(), &metadata)
+                .unwrap()
+                .into_iter()
+                .map(|s| (s.name.to_owned(), s.reason))
+                .collect();
+            reasons.sort_by(|a, b| a.0.cmp(&b.0));
+            assert_eq!(
+                reasons,
+                [
+                    (
+                        "a".to_owned(),
+                        SelectionReason::Unchanged("HEAD~1".to_owned())
+                    ),
+                    ("b".to_owned(), SelectionReason::DefaultMembers),
+                    ("c".to_owned(), SelectionReason::DefaultMembers),
+                    (
+                        "d".to_owned(),
+                        SelectionReason::Unchanged("HEAD~1".to_owned())
+                    ),
+                ]
+            );
+        }
+
+        #[test]
+        fn uncommitted_and_untracked() {
+            let repo = repo();
+            write(&repo, "a/src/lib.rs", "pub fn changed() {}");
+            write(&repo, "d/src/new.rs", "");
+            let workspace = crate::Workspace::builder().exclude("c").build();
+            let (included, excluded) = partition(&repo, &workspace, "HEAD");
+            assert_eq!(included, ["a", "b", "d"]);
+            assert_eq!(excluded, ["c"]);
+        }
+
+        #[test]
+        fn unknown_rev() {
+            let repo = repo();
+            let metadata = repo.metadata().unwrap();
+            let selection = Selection::builder().since("does-not-exist").build();
+            assert!(selection
+                .partition_packages(&Default::default(), &metadata)
+                .is_err());
+        }
+
+        #[test]
+        fn no_deps() {
+            let repo = repo();
+            let metadata = crate::CargoBinary::from_env()
+                .metadata()
+                .manifest_path(repo.manifest_path())
+                .no_deps()
+                .exec()
+                .unwrap();
+            let err = Selection::default()
+                .partition_packages(&Default::default(), &metadata)
+                .unwrap_err();
+            assert!(matches!(err, crate::Error::NoDeps), "{:?}", err);
+        }
+    }
+}
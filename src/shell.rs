@@ -0,0 +1,594 @@
+//! Cargo flags for verbosity and color, and status output styled like cargo's.
+
+use std::fmt;
+use std::io;
+use std::io::Write;
+
+#[derive(Default, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "kebab-case", default))]
+#[non_exhaustive]
+pub struct Verbosity {
+    /// Use verbose output (-vv very verbose)
+    pub verbose: u8,
+    /// Do not print cargo log messages
+    pub quiet: bool,
+    /// Coloring: auto, always, never
+    pub color: Option<ColorChoice>,
+}
+
+impl Verbosity {
+    /// How much a [`Shell`] should print
+    pub fn level(&self) -> VerbosityLevel {
+        if self.quiet {
+            VerbosityLevel::Quiet
+        } else if 0 < self.verbose {
+            VerbosityLevel::Verbose
+        } else {
+            VerbosityLevel::Normal
+        }
+    }
+
+    /// `--color`, otherwise `CARGO_TERM_COLOR` like `cargo`, otherwise [`ColorChoice::Auto`]
+    pub fn color_choice(&self) -> ColorChoice {
+        self.color
+            .or_else(|| {
+                std::env::var("CARGO_TERM_COLOR")
+                    .ok()
+                    .and_then(|color| color.parse().ok())
+            })
+            .unwrap_or_default()
+    }
+
+    /// A [`Shell`] writing to stderr with these flags
+    pub fn shell(&self) -> Shell {
+        let mut shell = Shell::new();
+        shell.set_verbosity(self.level());
+        shell.set_color_choice(self.color_choice());
+        shell
+    }
+}
+
+/// When to color output, see `--color`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "kebab-case"))]
+pub enum ColorChoice {
+    /// Color when writing to a terminal that supports it
+    Auto,
+    /// Always color
+    Always,
+    /// Never color
+    Never,
+}
+
+impl Default for ColorChoice {
+    fn default() -> Self {
+        ColorChoice::Auto
+    }
+}
+
+impl std::str::FromStr for ColorChoice {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "auto" => Ok(ColorChoice::Auto),
+            "always" => Ok(ColorChoice::Always),
+            "never" => Ok(ColorChoice::Never),
+            _ => Err(format!(
+                "argument for --color must be auto, always, or never, but found `{}`",
+                s
+            )),
+        }
+    }
+}
+
+impl fmt::Display for ColorChoice {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ColorChoice::Auto => "auto".fmt(f),
+            ColorChoice::Always => "always".fmt(f),
+            ColorChoice::Never => "never".fmt(f),
+        }
+    }
+}
+
+/// How much a [`Shell`] prints, see [`Verbosity::level`].
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum VerbosityLevel {
+    /// Only errors
+    Quiet,
+    /// Statuses, warnings and errors
+    Normal,
+    /// Also messages passed to [`Shell::verbose`]
+    Verbose,
+}
+
+impl Default for VerbosityLevel {
+    fn default() -> Self {
+        VerbosityLevel::Normal
+    }
+}
+
+/// Color of a [`Shell::status_with_color`] header.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Color {
+    /// Progress, like `Compiling`
+    Green,
+    /// Waiting or informational, like `Blocking`
+    Cyan,
+    /// Warnings
+    Yellow,
+    /// Errors
+    Red,
+}
+
+impl Color {
+    fn ansi(self) -> &'static str {
+        match self {
+            Color::Green => "\x1b[32m",
+            Color::Cyan => "\x1b[36m",
+            Color::Yellow => "\x1b[33m",
+            Color::Red => "\x1b[31m",
+        }
+    }
+}
+
+const BOLD: &str = "\x1b[1m";
+const RESET: &str = "\x1b[0m";
+
+/// Status output styled like `cargo`'s, e.g.
+///
+/// ```text
+///    Compiling foo v0.1.0
+/// warning: unused manifest key: package.foo
+/// error: could not find `Cargo.toml`
+/// ```
+///
+/// Headers are bold, statuses right-aligned to 12 columns.  Color is only used when enabled by
+/// [`ColorChoice`]; with [`ColorChoice::Auto`], that is when writing to a terminal and `TERM` is
+/// not `dumb`.  On Windows, `TERM` must be set since legacy consoles do not interpret ANSI
+/// escapes.  Detecting a terminal requires the feature `color`; without it, `Auto` is not colored.
+///
+/// ```rust
+/// let mut shell = clap_cargo::Verbosity::default().shell();
+/// shell.status("Checking", "foo v0.1.0").unwrap();
+/// shell.warn("unused manifest key: package.foo").unwrap();
+/// ```
+pub struct Shell {
+    output: Output,
+    verbosity: VerbosityLevel,
+    color_choice: ColorChoice,
+}
+
+enum Output {
+    Stderr,
+    Write(Box<dyn Write + Send>),
+}
+
+impl Shell {
+    /// Write to stderr
+    pub fn new() -> Self {
+        Self {
+            output: Output::Stderr,
+            verbosity: VerbosityLevel::default(),
+            color_choice: ColorChoice::default(),
+        }
+    }
+
+    /// Write to `out`, e.g. to capture output
+    ///
+    /// `out` is not treated as a terminal, so it is only colored with [`ColorChoice::Always`].
+    pub fn from_write(out: Box<dyn Write + Send>) -> Self {
+        Self {
+            output: Output::Write(out),
+            ..Self::new()
+        }
+    }
+
+    /// How much to print
+    pub fn verbosity(&self) -> VerbosityLevel {
+        self.verbosity
+    }
+
+    /// Change how much to print
+    pub fn set_verbosity(&mut self, verbosity: VerbosityLevel) {
+        self.verbosity = verbosity;
+    }
+
+    /// Change when to color output
+    pub fn set_color_choice(&mut self, color_choice: ColorChoice) {
+        self.color_choice = color_choice;
+    }
+
+    /// Whether output is colored
+    pub fn is_colored(&self) -> bool {
+        match self.color_choice {
+            ColorChoice::Always => true,
+            ColorChoice::Never => false,
+            ColorChoice::Auto => match self.output {
+                Output::Stderr => term_supports_color() && stderr_is_terminal(),
+                Output::Write(_) => false,
+            },
+        }
+    }
+
+    /// Print a green, right-aligned `status` followed by `message`, unless quiet
+    pub fn status(
+        &mut self,
+        status: impl fmt::Display,
+        message: impl fmt::Display,
+    ) -> io::Result<()> {
+        self.status_with_color(status, message, Color::Green)
+    }
+
+    /// Print a right-aligned `status` in `color` followed by `message`, unless quiet
+    pub fn status_with_color(
+        &mut self,
+        status: impl fmt::Display,
+        message: impl fmt::Display,
+        color: Color,
+    ) -> io::Result<()> {
+        if self.verbosity == VerbosityLevel::Quiet {
+            return Ok(());
+        }
+        self.print(&status, &message, color, true)
+    }
+
+    /// Print a `warning:`, unless quiet
+    pub fn warn(&mut self, message: impl fmt::Display) -> io::Result<()> {
+        if self.verbosity == VerbosityLevel::Quiet {
+            return Ok(());
+        }
+        self.print(&"warning", &message, Color::Yellow, false)
+    }
+
+    /// Print a `note:`, unless quiet
+    pub fn note(&mut self, message: impl fmt::Display) -> io::Result<()> {
+        if self.verbosity == VerbosityLevel::Quiet {
+            return Ok(());
+        }
+        self.print(&"note", &message, Color::Cyan, false)
+    }
+
+    /// Print an `error:`, even when quiet
+    pub fn error(&mut self, message: impl fmt::Display) -> io::Result<()> {
+        self.print(&"error", &message, Color::Red, false)
+    }
+
+    /// Call `callback` only when verbose
+    pub fn verbose(
+        &mut self,
+        callback: impl FnOnce(&mut Shell) -> io::Result<()>,
+    ) -> io::Result<()> {
+        match self.verbosity {
+            VerbosityLevel::Verbose => callback(self),
+            _ => Ok(()),
+        }
+    }
+
+    fn print(
+        &mut self,
+        header: &dyn fmt::Display,
+        message: &dyn fmt::Display,
+        color: Color,
+        justified: bool,
+    ) -> io::Result<()> {
+        let mut line = String::new();
+        let colored = self.is_colored();
+        if colored {
+            line.push_str(BOLD);
+            line.push_str(color.ansi());
+        }
+        if justified {
+            line.push_str(&format!("{:>12}", header));
+        } else {
+            line.push_str(&header.to_string());
+            if colored {
+                line.push_str(RESET);
+                line.push_str(BOLD);
+            }
+            line.push(':');
+        }
+        if colored {
+            line.push_str(RESET);
+        }
+        line.push_str(&format!(" {}\n", message));
+
+        match self.output {
+            Output::Stderr => io::stderr().lock().write_all(line.as_bytes()),
+            Output::Write(ref mut out) => {
+                out.write_all(line.as_bytes())?;
+                out.flush()
+            }
+        }
+    }
+}
+
+impl Default for Shell {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl fmt::Debug for Shell {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let output = match self.output {
+            Output::Stderr => "stderr",
+            Output::Write(_) => "write",
+        };
+        f.debug_struct("Shell")
+            .field("output", &output)
+            .field("verbosity", &self.verbosity)
+            .field("color_choice", &self.color_choice)
+            .finish()
+    }
+}
+
+#[cfg(feature = "color")]
+fn stderr_is_terminal() -> bool {
+    is_terminal::IsTerminal::is_terminal(&std::io::stderr())
+}
+
+#[cfg(not(feature = "color"))]
+fn stderr_is_terminal() -> bool {
+    false
+}
+
+/// Whether `TERM` allows ANSI escapes
+///
+/// Legacy Windows consoles print them as-is unless virtual terminal processing is enabled, so
+/// on Windows only terminals that set `TERM`, like mintty, are colored.
+fn term_supports_color() -> bool {
+    match std::env::var_os("TERM") {
+        Some(term) => term != "dumb",
+        None => !cfg!(windows),
+    }
+}
+
+crate::args::flag_group_args!(VerbosityArgs for Verbosity { "verbose", "quiet", "color" });
+
+impl VerbosityArgs {
+    /// Register the flags on `cmd`.
+    pub fn augment_args<'help>(&'help self, cmd: clap::Command<'help>) -> clap::Command<'help> {
+        let names = &self.names;
+        cmd.arg(
+            names
+                .short(names.arg("verbose"), 'v')
+                .multiple_occurrences(true)
+                .help("Use verbose output (-vv very verbose)"),
+        )
+        .arg(
+            names
+                .short(names.arg("quiet"), 'q')
+                .conflicts_with(names.get("verbose"))
+                .help("Do not print cargo log messages"),
+        )
+        .arg(
+            names
+                .arg("color")
+                .value_name("WHEN")
+                .possible_values(["auto", "always", "never"])
+                .help("Coloring: auto, always, never"),
+        )
+    }
+
+    /// Read the flags registered by [`VerbosityArgs::augment_args`] from `matches`.
+    pub fn from_arg_matches(&self, matches: &clap::ArgMatches) -> Verbosity {
+        let names = &self.names;
+        Verbosity {
+            verbose: matches
+                .occurrences_of(names.get("verbose"))
+                .min(u8::MAX as u64) as u8,
+            quiet: names.flag(matches, "quiet"),
+            color: names
+                .value(matches, "color")
+                .and_then(|color| color.parse().ok()),
+        }
+    }
+}
+
+impl Verbosity {
+    /// Build [`Verbosity`] flags programmatically.
+    ///
+    /// ```rust
+    /// let verbosity = clap_cargo::Verbosity::builder()
+    ///     .color(clap_cargo::ColorChoice::Never)
+    ///     .build();
+    /// assert_eq!(verbosity.color_choice(), clap_cargo::ColorChoice::Never);
+    /// ```
+    pub fn builder() -> VerbosityBuilder {
+        VerbosityBuilder::default()
+    }
+}
+
+/// Builder for [`Verbosity`], see [`Verbosity::builder`].
+#[derive(Default, Clone, Debug, PartialEq, Eq)]
+pub struct VerbosityBuilder {
+    inner: Verbosity,
+}
+
+impl VerbosityBuilder {
+    /// Use verbose output, `2` for very verbose
+    pub fn verbose(mut self, verbose: u8) -> Self {
+        self.inner.verbose = verbose;
+        self
+    }
+
+    /// Do not print cargo log messages
+    pub fn quiet(mut self, yes: bool) -> Self {
+        self.inner.quiet = yes;
+        self
+    }
+
+    /// When to color output
+    pub fn color(mut self, color: ColorChoice) -> Self {
+        self.inner.color = Some(color);
+        self
+    }
+
+    /// Finish building the flags
+    pub fn build(self) -> Verbosity {
+        self.inner
+    }
+}
+
+impl From<VerbosityBuilder> for Verbosity {
+    fn from(builder: VerbosityBuilder) -> Self {
+        builder.build()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use clap::StructOpt;
+    use std::sync::{Arc, Mutex};
+
+    #[derive(Debug, StructOpt)]
+    struct Args {
+        #[clap(flatten)]
+        verbosity: Verbosity,
+    }
+
+    #[test]
+    fn verify_app() {
+        use clap::CommandFactory;
+        Args::command().debug_assert()
+    }
+
+    #[test]
+    fn parse() {
+        let args = Args::parse_from(["test", "-vv", "--color", "never"]);
+        assert_eq!(
+            args.verbosity,
+            Verbosity {
+                verbose: 2,
+                quiet: false,
+                color: Some(ColorChoice::Never),
+            }
+        );
+        assert_eq!(args.verbosity.level(), VerbosityLevel::Verbose);
+
+        let args = Args::parse_from(["test", "-q"]);
+        assert_eq!(args.verbosity.level(), VerbosityLevel::Quiet);
+
+        assert!(Args::try_parse_from(["test", "-q", "-v"]).is_err());
+        assert!(Args::try_parse_from(["test", "--color", "sometimes"]).is_err());
+    }
+
+    #[derive(Clone, Default)]
+    struct Buffer(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for Buffer {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl Buffer {
+        fn take(&self) -> String {
+            String::from_utf8(std::mem::take(&mut *self.0.lock().unwrap())).unwrap()
+        }
+    }
+
+    fn shell() -> (Shell, Buffer) {
+        let buffer = Buffer::default();
+        (Shell::from_write(Box::new(buffer.clone())), buffer)
+    }
+
+    #[test]
+    fn plain() {
+        let (mut shell, buffer) = shell();
+        assert!(!shell.is_colored());
+        shell.status("Compiling", "foo v0.1.0").unwrap();
+        shell.status("Documenting", "foo v0.1.0").unwrap();
+        shell.warn("unused manifest key").unwrap();
+        shell.note("see --help").unwrap();
+        shell.error("could not compile `foo`").unwrap();
+        shell.verbose(|shell| shell.status("Fresh", "foo")).unwrap();
+        assert_eq!(
+            buffer.take(),
+            "   Compiling foo v0.1.0
+ Documenting foo v0.1.0
+warning: unused manifest key
+note: see --help
+error: could not compile `foo`
+"
+        );
+    }
+
+    #[test]
+    fn colored() {
+        let (mut shell, buffer) = shell();
+        shell.set_color_choice(ColorChoice::Always);
+        shell.status("Finished", "dev").unwrap();
+        shell.warn("careful").unwrap();
+        shell.error("failed").unwrap();
+        assert_eq!(
+            buffer.take(),
+            "\x1b[1m\x1b[32m    Finished\x1b[0m dev
+\x1b[1m\x1b[33mwarning\x1b[0m\x1b[1m:\x1b[0m careful
+\x1b[1m\x1b[31merror\x1b[0m\x1b[1m:\x1b[0m failed
+"
+        );
+    }
+
+    #[test]
+    fn quiet() {
+        let (mut shell, buffer) = shell();
+        shell.set_verbosity(VerbosityLevel::Quiet);
+        shell.status("Checking", "foo").unwrap();
+        shell.warn("careful").unwrap();
+        shell.verbose(|shell| shell.error("unreachable")).unwrap();
+        shell.error("failed").unwrap();
+        assert_eq!(buffer.take(), "error: failed\n");
+    }
+
+    #[test]
+    fn verbose() {
+        let (mut shell, buffer) = shell();
+        shell.set_verbosity(VerbosityLevel::Verbose);
+        shell
+            .verbose(|shell| shell.status("Running", "`rustc`"))
+            .unwrap();
+        assert_eq!(buffer.take(), "     Running `rustc`\n");
+    }
+
+    #[test]
+    fn args_with_prefix() {
+        let args = VerbosityArgs::with_prefix("base-");
+        let cmd = args.augment_args(clap::Command::new("test"));
+        cmd.clone().debug_assert();
+
+        let matches = cmd
+            .clone()
+            .try_get_matches_from([
+                "test",
+                "--base-verbose",
+                "--base-verbose",
+                "--base-color",
+                "always",
+            ])
+            .unwrap();
+        assert_eq!(
+            args.from_arg_matches(&matches),
+            Verbosity {
+                verbose: 2,
+                quiet: false,
+                color: Some(ColorChoice::Always),
+            }
+        );
+        assert!(cmd.clone().try_get_matches_from(["test", "-v"]).is_err());
+        assert!(cmd
+            .try_get_matches_from(["test", "--base-verbose", "--base-quiet"])
+            .is_err());
+    }
+}
@@ -0,0 +1,175 @@
+//! Selecting targets by their `required-features`.
+
+/// A target skipped because some of its `required-features` are not enabled.
+///
+/// Like `cargo`, this is only worth reporting as an error when the target was explicitly
+/// requested.
+///
+/// Note: Requires the feature `cargo_metadata`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct SkippedTarget<'m> {
+    pub package: &'m cargo_metadata::Package,
+    pub target: &'m cargo_metadata::Target,
+    /// Entries of `required-features` that are not enabled
+    pub missing_features: Vec<String>,
+}
+
+impl std::fmt::Display for SkippedTarget<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let quoted: Vec<_> = self
+            .missing_features
+            .iter()
+            .map(|feature| format!("`{}`", feature))
+            .collect();
+        write!(
+            f,
+            "target `{}` in package `{}` requires the features: {}\n\
+             Consider enabling them by passing, e.g., `--features=\"{}\"`",
+            self.target.name,
+            self.package.name,
+            quoted.join(", "),
+            self.missing_features.join(" ")
+        )
+    }
+}
+
+impl std::error::Error for SkippedTarget<'_> {}
+
+impl crate::Features {
+    /// Partition `package`'s targets into those whose `required-features` are enabled by these
+    /// flags and those skipped.
+    ///
+    /// Features of `package` are resolved with [`Features::resolve`][crate::Features::resolve].
+    /// `dep/feat` entries are checked against the features of `dep` in `Metadata::resolve`, so
+    /// forward these flags with [`Features::forward_metadata`][crate::Features::forward_metadata]
+    /// for them to be accurate.
+    ///
+    /// Note: Requires the feature `cargo_metadata`.
+    pub fn partition_targets<'m>(
+        &self,
+        meta: &'m cargo_metadata::Metadata,
+        package: &'m cargo_metadata::Package,
+    ) -> (Vec<&'m cargo_metadata::Target>, Vec<SkippedTarget<'m>>) {
+        let enabled = self.resolve(&crate::MetadataGraph::new(meta), &package.id);
+        let mut included = Vec::new();
+        let mut skipped = Vec::new();
+        for target in &package.targets {
+            let missing_features: Vec<_> = target
+                .required_features
+                .iter()
+                .filter(|required| match required.split_once('/') {
+                    Some((dep, feature)) => !dependency_feature(meta, package, dep, feature),
+                    None => !enabled.contains(required.as_str()),
+                })
+                .cloned()
+                .collect();
+            if missing_features.is_empty() {
+                included.push(target);
+            } else {
+                skipped.push(SkippedTarget {
+                    package,
+                    target,
+                    missing_features,
+                });
+            }
+        }
+        (included, skipped)
+    }
+}
+
+/// Whether the dependency `dep` of `package` is resolved with `feature`
+fn dependency_feature(
+    meta: &cargo_metadata::Metadata,
+    package: &cargo_metadata::Package,
+    dep: &str,
+    feature: &str,
+) -> bool {
+    let resolve = match meta.resolve.as_ref() {
+        Some(resolve) => resolve,
+        None => return false,
+    };
+    let node = |id: &cargo_metadata::PackageId| resolve.nodes.iter().find(|n| n.id == *id);
+    let dep_name = dep.replace('-', "_");
+    node(&package.id)
+        .into_iter()
+        .flat_map(|n| n.deps.iter())
+        .filter(|d| d.name == dep_name)
+        .filter_map(|d| node(&d.pkg))
+        .any(|n| n.features.iter().any(|f| f == feature))
+}
+
+#[cfg(test)]
+mod test {
+    fn metadata(features: &crate::Features) -> cargo_metadata::Metadata {
+        let mut metadata = cargo_metadata::MetadataCommand::new();
+        metadata.manifest_path("tests/fixtures/required_features/Cargo.toml");
+        features.forward_metadata(&mut metadata);
+        metadata.exec().unwrap()
+    }
+
+    fn partition(features: &crate::Features) -> (Vec<String>, Vec<(String, Vec<String>)>) {
+        let meta = metadata(features);
+        let package = meta.packages.iter().find(|p| p.name == "targets").unwrap();
+        let (included, skipped) = features.partition_targets(&meta, package);
+        let mut included: Vec<_> = included.iter().map(|t| t.name.clone()).collect();
+        included.sort();
+        (
+            included,
+            skipped
+                .iter()
+                .map(|s| (s.target.name.clone(), s.missing_features.clone()))
+                .collect(),
+        )
+    }
+
+    #[test]
+    fn default_features() {
+        let (included, skipped) = partition(&crate::Features::default());
+        assert_eq!(included, ["plain", "std-example", "targets"]);
+        assert_eq!(
+            skipped,
+            [
+                ("extra-tool".to_owned(), vec!["extra".to_owned()]),
+                ("fast".to_owned(), vec!["helper/fast".to_owned()]),
+            ]
+        );
+    }
+
+    #[test]
+    fn no_default_features() {
+        let features = crate::Features::builder()
+            .no_default_features(true)
+            .features(vec!["extra", "helper/fast"])
+            .build();
+        let (included, skipped) = partition(&features);
+        assert_eq!(included, ["extra-tool", "plain", "targets"]);
+        assert_eq!(
+            skipped,
+            [
+                ("std-example".to_owned(), vec!["std".to_owned()]),
+                ("fast".to_owned(), vec!["std".to_owned()]),
+            ]
+        );
+    }
+
+    #[test]
+    fn all_features() {
+        let features = crate::Features::builder().all_features(true).build();
+        let (included, skipped) = partition(&features);
+        assert_eq!(included.len(), 5);
+        assert_eq!(skipped, []);
+    }
+
+    #[test]
+    fn display() {
+        let meta = metadata(&crate::Features::default());
+        let package = meta.packages.iter().find(|p| p.name == "targets").unwrap();
+        let (_, skipped) = crate::Features::default().partition_targets(&meta, package);
+        assert_eq!(
+            skipped[0].to_string(),
+            "target `extra-tool` in package `targets` requires the features: `extra`\n\
+             Consider enabling them by passing, e.g., `--features=\"extra\"`"
+        );
+    }
+}
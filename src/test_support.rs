@@ -0,0 +1,24 @@
+//! Helpers shared by the crate's own tests.
+
+/// Run `cmd` in a temporary target directory, failing on any error `cargo` reports
+#[cfg(feature = "escargot")]
+pub(crate) fn build(cmd: escargot::CargoBuild) -> Result<(), escargot::error::CargoError> {
+    let target_dir = tempfile::tempdir().unwrap();
+    for msg in cmd.target_dir(target_dir.path()).exec()? {
+        msg?;
+    }
+    Ok(())
+}
+
+/// Run `git` in `dir` with a fixed identity, for committing test repositories
+#[cfg(feature = "cargo_metadata")]
+pub(crate) fn git(dir: &std::path::Path, args: &[&str]) {
+    let status = std::process::Command::new("git")
+        .args(["-c", "user.name=test", "-c", "user.email=test@example.com"])
+        .args(args)
+        .current_dir(dir)
+        .stdout(std::process::Stdio::null())
+        .status()
+        .unwrap();
+    assert!(status.success());
+}
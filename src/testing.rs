@@ -0,0 +1,518 @@
+//! Scaffolding for testing `cargo` plugins.
+//!
+//! Describe a workspace with [`WorkspaceSpec`], then either write it to a temporary directory
+//! with [`WorkspaceSpec::create`] to run `cargo` against, or turn it directly into
+//! `cargo_metadata::Metadata` with [`WorkspaceSpec::metadata`].
+//!
+//! ```rust
+//! use clap_cargo::testing::{PackageSpec, WorkspaceSpec};
+//!
+//! let spec = WorkspaceSpec::new()
+//!     .member(PackageSpec::new("base").feature("std", &[]))
+//!     .member(PackageSpec::new("app").dependency("base"));
+//! let metadata = spec.metadata();
+//! assert_eq!(metadata.workspace_members.len(), 2);
+//! ```
+//!
+//! Note: Requires the feature `testing`.
+
+use std::collections;
+use std::fs;
+use std::io;
+use std::path;
+
+/// A virtual workspace whose members only depend on each other.
+///
+/// Note: Requires the feature `testing`.
+#[derive(Default, Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct WorkspaceSpec {
+    /// Packages in the workspace, each in a directory named after it
+    pub members: Vec<PackageSpec>,
+    /// Names of the members in `workspace.default-members`
+    pub default_members: Vec<String>,
+}
+
+/// A workspace member.
+///
+/// Note: Requires the feature `testing`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct PackageSpec {
+    pub name: String,
+    pub version: String,
+    /// Dependencies on other workspace members, through `path`
+    pub dependencies: Vec<DependencySpec>,
+    /// The `[features]` table
+    pub features: collections::BTreeMap<String, Vec<String>>,
+}
+
+/// A dependency on another workspace member.
+///
+/// Note: Requires the feature `testing`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct DependencySpec {
+    /// Name of the workspace member depended on
+    pub name: String,
+    pub kind: cargo_metadata::DependencyKind,
+    pub optional: bool,
+}
+
+impl WorkspaceSpec {
+    /// An empty workspace
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add `package` to the workspace
+    pub fn member(mut self, package: PackageSpec) -> Self {
+        self.members.push(package);
+        self
+    }
+
+    /// Add the member `name` to `workspace.default-members`
+    pub fn default_member(mut self, name: impl Into<String>) -> Self {
+        self.default_members.push(name.into());
+        self
+    }
+
+    /// Write the workspace to a new temporary directory.
+    ///
+    /// Each member gets an empty `src/lib.rs`.
+    pub fn create(&self) -> io::Result<TempWorkspace> {
+        let dir = tempfile::tempdir()?;
+        self.create_at(dir.path())?;
+        Ok(TempWorkspace { dir })
+    }
+
+    /// Write the workspace to `root`, like [`WorkspaceSpec::create`].
+    ///
+    /// Useful for placing several workspaces within one [`TempWorkspace`].
+    pub fn create_at(&self, root: &path::Path) -> io::Result<()> {
+        fs::create_dir_all(root)?;
+        fs::write(root.join("Cargo.toml"), self.workspace_manifest())?;
+        for package in &self.members {
+            let dir = root.join(&package.name);
+            fs::create_dir_all(dir.join("src"))?;
+            fs::write(dir.join("Cargo.toml"), package.manifest())?;
+            fs::write(dir.join("src/lib.rs"), "")?;
+        }
+        Ok(())
+    }
+
+    /// Describe the workspace like `cargo metadata` would for it at `/workspace`, without running
+    /// `cargo`.
+    ///
+    /// See [`WorkspaceSpec::metadata_at`].
+    pub fn metadata(&self) -> cargo_metadata::Metadata {
+        self.metadata_at(path::Path::new("/workspace"))
+    }
+
+    /// Describe the workspace like `cargo metadata` would for it at `root`, without running
+    /// `cargo`.
+    ///
+    /// Like `cargo metadata` without any feature flags, only features enabled through `default`
+    /// are resolved and only optional dependencies they enable are part of the resolve.
+    /// `cargo_metadata` does not report `default-members`.
+    pub fn metadata_at(&self, root: &path::Path) -> cargo_metadata::Metadata {
+        let root = root.to_string_lossy();
+        let root = root.trim_end_matches('/');
+        let ids: collections::HashMap<_, _> = self
+            .members
+            .iter()
+            .map(|p| (p.name.as_str(), p.id(root)))
+            .collect();
+
+        let packages: Vec<_> = self.members.iter().map(|p| p.package(root)).collect();
+        let nodes: Vec<_> = self.members.iter().map(|p| p.node(&ids)).collect();
+        let members: Vec<_> = self.members.iter().map(|p| &ids[p.name.as_str()]).collect();
+        let metadata = serde_json::json!({
+            "packages": packages,
+            "workspace_members": members,
+            "resolve": {
+                "nodes": nodes,
+                "root": null,
+            },
+            "workspace_root": root,
+            "target_directory": format!("{}/target", root),
+            "version": 1,
+        });
+        serde_json::from_value(metadata).expect("generated metadata matches `cargo_metadata`")
+    }
+
+    fn workspace_manifest(&self) -> String {
+        let mut manifest = String::from("[workspace]\nresolver = \"2\"\n");
+        manifest.push_str(&format!(
+            "members = {}\n",
+            toml_list(self.members.iter().map(|p| &p.name))
+        ));
+        if !self.default_members.is_empty() {
+            manifest.push_str(&format!(
+                "default-members = {}\n",
+                toml_list(self.default_members.iter())
+            ));
+        }
+        manifest
+    }
+}
+
+impl PackageSpec {
+    /// A package at version `0.1.0` without dependencies or features
+    pub fn new(name: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            version: "0.1.0".to_owned(),
+            dependencies: Vec::new(),
+            features: collections::BTreeMap::new(),
+        }
+    }
+
+    /// Set the package's version
+    pub fn version(mut self, version: impl Into<String>) -> Self {
+        self.version = version.into();
+        self
+    }
+
+    /// Depend on the member `name`
+    pub fn dependency(self, name: impl Into<String>) -> Self {
+        self.add_dependency(name, cargo_metadata::DependencyKind::Normal, false)
+    }
+
+    /// Optionally depend on the member `name`
+    pub fn optional_dependency(self, name: impl Into<String>) -> Self {
+        self.add_dependency(name, cargo_metadata::DependencyKind::Normal, true)
+    }
+
+    /// Dev-depend on the member `name`
+    pub fn dev_dependency(self, name: impl Into<String>) -> Self {
+        self.add_dependency(name, cargo_metadata::DependencyKind::Development, false)
+    }
+
+    /// Build-depend on the member `name`
+    pub fn build_dependency(self, name: impl Into<String>) -> Self {
+        self.add_dependency(name, cargo_metadata::DependencyKind::Build, false)
+    }
+
+    /// Declare the feature `name`, enabling `values`
+    pub fn feature(mut self, name: impl Into<String>, values: &[&str]) -> Self {
+        self.features.insert(
+            name.into(),
+            values.iter().map(|v| (*v).to_owned()).collect(),
+        );
+        self
+    }
+
+    fn add_dependency(
+        mut self,
+        name: impl Into<String>,
+        kind: cargo_metadata::DependencyKind,
+        optional: bool,
+    ) -> Self {
+        self.dependencies.push(DependencySpec {
+            name: name.into(),
+            kind,
+            optional,
+        });
+        self
+    }
+
+    fn manifest(&self) -> String {
+        let mut manifest = format!(
+            "[package]\nname = {:?}\nversion = {:?}\nedition = \"2018\"\n",
+            self.name, self.version
+        );
+        if !self.features.is_empty() {
+            manifest.push_str("\n[features]\n");
+            for (name, values) in &self.features {
+                manifest.push_str(&format!("{:?} = {}\n", name, toml_list(values.iter())));
+            }
+        }
+        for (kind, table) in &[
+            (cargo_metadata::DependencyKind::Normal, "dependencies"),
+            (
+                cargo_metadata::DependencyKind::Development,
+                "dev-dependencies",
+            ),
+            (cargo_metadata::DependencyKind::Build, "build-dependencies"),
+        ] {
+            let deps: Vec<_> = self
+                .dependencies
+                .iter()
+                .filter(|d| d.kind == *kind)
+                .collect();
+            if deps.is_empty() {
+                continue;
+            }
+            manifest.push_str(&format!("\n[{}]\n", table));
+            for dep in deps {
+                manifest.push_str(&format!(
+                    "{:?} = {{ path = \"../{}\", optional = {} }}\n",
+                    dep.name, dep.name, dep.optional
+                ));
+            }
+        }
+        manifest
+    }
+
+    fn id(&self, root: &str) -> String {
+        format!(
+            "{} {} (path+file://{}/{})",
+            self.name, self.version, root, self.name
+        )
+    }
+
+    /// Features, including the implicit ones for optional dependencies
+    fn all_features(&self) -> collections::BTreeMap<String, Vec<String>> {
+        let mut features = self.features.clone();
+        for dep in self.dependencies.iter().filter(|d| d.optional) {
+            let explicit = format!("dep:{}", dep.name);
+            let uses_explicit = self.features.values().flatten().any(|v| *v == explicit);
+            if !uses_explicit {
+                features
+                    .entry(dep.name.clone())
+                    .or_insert_with(|| vec![explicit]);
+            }
+        }
+        features
+    }
+
+    /// Features enabled through `default` and the optional dependencies they enable
+    fn default_features(&self) -> (collections::BTreeSet<String>, collections::BTreeSet<String>) {
+        let features = self.all_features();
+        let mut enabled = collections::BTreeSet::new();
+        let mut dependencies = collections::BTreeSet::new();
+        let mut pending = vec!["default".to_owned()];
+        while let Some(value) = pending.pop() {
+            if let Some(dep) = value.strip_prefix("dep:") {
+                dependencies.insert(dep.to_owned());
+            } else if let Some((dep, _)) = value.split_once('/') {
+                if !dep.ends_with('?') {
+                    pending.push(dep.to_owned());
+                }
+            } else if let Some(values) = features.get(&value) {
+                if enabled.insert(value) {
+                    pending.extend(values.iter().cloned());
+                }
+            }
+        }
+        (enabled, dependencies)
+    }
+
+    fn package(&self, root: &str) -> serde_json::Value {
+        let dir = format!("{}/{}", root, self.name);
+        let dependencies: Vec<_> = self
+            .dependencies
+            .iter()
+            .map(|dep| {
+                serde_json::json!({
+                    "name": dep.name,
+                    "source": null,
+                    "req": "*",
+                    "kind": kind_name(dep.kind),
+                    "optional": dep.optional,
+                    "uses_default_features": true,
+                    "features": [],
+                    "target": null,
+                    "rename": null,
+                    "registry": null,
+                    "path": format!("{}/{}", root, dep.name),
+                })
+            })
+            .collect();
+        serde_json::json!({
+            "name": self.name,
+            "version": self.version,
+            "id": self.id(root),
+            "source": null,
+            "description": null,
+            "dependencies": dependencies,
+            "license": null,
+            "license_file": null,
+            "targets": [{
+                "name": self.name.replace('-', "_"),
+                "kind": ["lib"],
+                "crate_types": ["lib"],
+                "required-features": [],
+                "src_path": format!("{}/src/lib.rs", dir),
+                "edition": "2018",
+            }],
+            "features": self.all_features(),
+            "manifest_path": format!("{}/Cargo.toml", dir),
+            "readme": null,
+            "repository": null,
+            "homepage": null,
+            "documentation": null,
+            "edition": "2018",
+            "links": null,
+            "publish": null,
+            "default_run": null,
+            "rust_version": null,
+        })
+    }
+
+    fn node(&self, ids: &collections::HashMap<&str, String>) -> serde_json::Value {
+        let (features, optional) = self.default_features();
+        // One entry per package depended on, listing every kind it is depended on through
+        let mut deps: collections::BTreeMap<&str, Vec<serde_json::Value>> =
+            collections::BTreeMap::new();
+        for dep in &self.dependencies {
+            if dep.optional && !optional.contains(&dep.name) {
+                continue;
+            }
+            deps.entry(dep.name.as_str())
+                .or_default()
+                .push(serde_json::json!({
+                    "kind": kind_name(dep.kind),
+                    "target": null,
+                }));
+        }
+        let dependencies: Vec<_> = deps.keys().map(|name| &ids[name]).collect();
+        let deps: Vec<_> = deps
+            .iter()
+            .map(|(name, dep_kinds)| {
+                serde_json::json!({
+                    "name": name.replace('-', "_"),
+                    "pkg": ids[name],
+                    "dep_kinds": dep_kinds,
+                })
+            })
+            .collect();
+        serde_json::json!({
+            "id": ids[self.name.as_str()],
+            "deps": deps,
+            "dependencies": dependencies,
+            "features": features,
+        })
+    }
+}
+
+/// A workspace written by [`WorkspaceSpec::create`], deleted when dropped.
+///
+/// Note: Requires the feature `testing`.
+#[derive(Debug)]
+pub struct TempWorkspace {
+    dir: tempfile::TempDir,
+}
+
+impl TempWorkspace {
+    /// Directory containing the workspace
+    pub fn root(&self) -> &path::Path {
+        self.dir.path()
+    }
+
+    /// Path to the workspace's `Cargo.toml`
+    pub fn manifest_path(&self) -> path::PathBuf {
+        self.root().join("Cargo.toml")
+    }
+
+    /// Run `cargo metadata` on the workspace
+    pub fn metadata(&self) -> cargo_metadata::Result<cargo_metadata::Metadata> {
+        let cargo = crate::CargoBinary::from_env();
+        let mut cmd = cargo.metadata();
+        cmd.manifest_path(self.manifest_path());
+        cargo.exec_metadata(&cmd)
+    }
+}
+
+fn kind_name(kind: cargo_metadata::DependencyKind) -> Option<&'static str> {
+    match kind {
+        cargo_metadata::DependencyKind::Development => Some("dev"),
+        cargo_metadata::DependencyKind::Build => Some("build"),
+        _ => None,
+    }
+}
+
+fn toml_list<'s>(values: impl Iterator<Item = &'s String>) -> String {
+    let values: Vec<_> = values.map(|v| format!("{:?}", v)).collect();
+    format!("[{}]", values.join(", "))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn spec() -> WorkspaceSpec {
+        WorkspaceSpec::new()
+            .member(
+                PackageSpec::new("base")
+                    .feature("default", &["std"])
+                    .feature("std", &[]),
+            )
+            .member(
+                PackageSpec::new("app")
+                    .version("1.2.3")
+                    .dependency("base")
+                    .optional_dependency("extra")
+                    .dev_dependency("testing")
+                    .feature("default", &["extra"]),
+            )
+            .member(PackageSpec::new("extra"))
+            .member(PackageSpec::new("testing").dependency("base"))
+            .default_member("app")
+    }
+
+    /// Names, dependencies, and enabled features of each package
+    fn summary(meta: &cargo_metadata::Metadata) -> Vec<(String, Vec<String>, Vec<String>)> {
+        let resolve = meta.resolve.as_ref().unwrap();
+        let name = |id: &cargo_metadata::PackageId| {
+            meta.packages
+                .iter()
+                .find(|p| p.id == *id)
+                .unwrap()
+                .name
+                .clone()
+        };
+        let mut summary: Vec<_> = resolve
+            .nodes
+            .iter()
+            .map(|node| {
+                let mut deps: Vec<_> = node
+                    .deps
+                    .iter()
+                    .flat_map(|dep| {
+                        let name = name(&dep.pkg);
+                        dep.dep_kinds
+                            .iter()
+                            .map(move |k| format!("{} ({:?})", name, k.kind))
+                    })
+                    .collect();
+                deps.sort();
+                (name(&node.id), deps, node.features.clone())
+            })
+            .collect();
+        summary.sort();
+        summary
+    }
+
+    #[test]
+    fn metadata_matches_cargo() {
+        let spec = spec();
+        let workspace = spec.create().unwrap();
+        let actual = workspace.metadata().unwrap();
+        let expected = spec.metadata_at(workspace.root());
+        assert_eq!(summary(&expected), summary(&actual));
+        assert_eq!(expected.workspace_members.len(), 4);
+
+        let app = expected.packages.iter().find(|p| p.name == "app").unwrap();
+        assert_eq!(app.version.to_string(), "1.2.3");
+        assert_eq!(app.features["extra"], ["dep:extra"]);
+    }
+
+    #[test]
+    fn default_members() {
+        let workspace = spec().create().unwrap();
+        let manifest = fs::read_to_string(workspace.manifest_path()).unwrap();
+        assert!(manifest.contains("default-members = [\"app\"]"));
+    }
+
+    #[test]
+    fn unused_optional_dependency() {
+        let spec = WorkspaceSpec::new()
+            .member(PackageSpec::new("app").optional_dependency("extra"))
+            .member(PackageSpec::new("extra"));
+        let meta = spec.metadata();
+        let summary = summary(&meta);
+        assert_eq!(summary[0], ("app".to_owned(), vec![], vec![]));
+    }
+}
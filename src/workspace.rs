@@ -2,30 +2,68 @@
 
 use std::collections;
 
-#[derive(Default, Clone, Debug, PartialEq, Eq, clap::Args)]
+#[derive(Default, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "kebab-case", default))]
 #[non_exhaustive]
 pub struct Workspace {
-    #[clap(short, long, value_name = "SPEC")]
     /// Package to process (see `cargo help pkgid`)
     pub package: Vec<String>,
-    #[clap(long)]
     /// Process all packages in the workspace
     pub workspace: bool,
-    #[clap(long, hide_short_help(true), hide_long_help(true))]
     /// Process all packages in the workspace
     pub all: bool,
-    #[clap(long, value_name = "SPEC")]
     /// Exclude packages from being processed
     pub exclude: Vec<String>,
+    /// Also process workspace members that depend on the selected packages
+    pub with_dependents: bool,
+    /// Also process workspace members that the selected packages depend on
+    pub with_dependencies: bool,
+}
+
+impl Workspace {
+    /// Fill in anything not selected in `self` from `defaults`.
+    ///
+    /// When `self` selected packages with `--package`, the default selection is ignored:
+    /// - `--package` from `self` replaces the default list
+    /// - `--workspace` / `--all` / `--exclude` are only taken from `defaults` when `self` has no
+    ///   `--package`, with `--exclude` from both combined
+    /// - `--package` is only taken from `defaults` when `self` also has no `--workspace`, `--all`,
+    ///   or `--exclude`, since those already select from the whole workspace
+    ///
+    /// Regardless of `--package`, `--with-dependents` / `--with-dependencies` are enabled if
+    /// either enables them.  These flags have no negative form, so a default of `true` cannot be
+    /// turned off from the command-line.
+    pub fn with_defaults(mut self, defaults: &Workspace) -> Self {
+        self.with_dependents |= defaults.with_dependents;
+        self.with_dependencies |= defaults.with_dependencies;
+        if self.package.is_empty() {
+            if !self.workspace && !self.all && self.exclude.is_empty() {
+                self.package = defaults.package.clone();
+            }
+            self.workspace |= defaults.workspace;
+            self.all |= defaults.all;
+            for exclude in &defaults.exclude {
+                if !self.exclude.contains(exclude) {
+                    self.exclude.push(exclude.clone());
+                }
+            }
+        }
+        self
+    }
 }
 
 #[cfg(feature = "cargo_metadata")]
 impl Workspace {
     /// Partition workspace members into those selected and those excluded.
     ///
-    /// Notes:
-    /// - Requires the features `cargo_metadata`.
-    /// - Requires not calling `MetadataCommand::no_deps`
+    /// `--with-dependents` and `--with-dependencies` expand the selection to other workspace
+    /// members along any kind of dependency; `--exclude` still applies to them.  See
+    /// [`Workspace::partition_packages_with`] to limit how the selection is expanded.
+    /// Without dependency information (`MetadataCommand::no_deps`), they do not expand the
+    /// selection; check for it with [`Workspace::validate`].
+    ///
+    /// Note: Requires the features `cargo_metadata`.
     pub fn partition_packages<'m>(
         &self,
         meta: &'m cargo_metadata::Metadata,
@@ -33,45 +71,205 @@ impl Workspace {
         Vec<&'m cargo_metadata::Package>,
         Vec<&'m cargo_metadata::Package>,
     ) {
+        self.partition_packages_with(meta, &crate::WalkOptions::default())
+    }
+
+    /// Partition workspace members like [`Workspace::partition_packages`], expanding
+    /// `--with-dependents` and `--with-dependencies` according to `options`.
+    ///
+    /// Note: Requires the features `cargo_metadata`.
+    pub fn partition_packages_with<'m>(
+        &self,
+        meta: &'m cargo_metadata::Metadata,
+        options: &crate::WalkOptions,
+    ) -> (
+        Vec<&'m cargo_metadata::Package>,
+        Vec<&'m cargo_metadata::Package>,
+    ) {
+        let graph = crate::MetadataGraph::new(meta);
+        let (included, _) = self.partition_graph(&graph, options);
+        let included: collections::HashSet<_> = included.into_iter().collect();
+        meta.packages.iter().partition(|p| included.contains(&p.id))
+    }
+
+    /// Check that `meta` has dependency information and that each `--package` matches a
+    /// workspace member, like `cargo`.
+    ///
+    /// [`Workspace::partition_packages`] silently ignores unmatched specs and does not expand
+    /// `--with-dependents` or `--with-dependencies` without dependency information.
+    ///
+    /// Note: Requires the features `cargo_metadata`.
+    pub fn validate(&self, meta: &cargo_metadata::Metadata) -> Result<(), crate::Error> {
+        if meta.resolve.is_none() {
+            return Err(crate::Error::NoDeps);
+        }
+        self.validate_graph(&crate::MetadataGraph::new(meta))
+    }
+}
+
+impl Workspace {
+    /// Check that each `--package` matches a member of any [`WorkspaceGraph`][crate::WorkspaceGraph].
+    pub fn validate_graph<G: crate::WorkspaceGraph>(&self, graph: &G) -> Result<(), crate::Error> {
+        let members: Vec<_> = graph
+            .workspace_members()
+            .into_iter()
+            .map(|id| graph.package_name(id))
+            .collect();
+        // Deviating from cargo by not supporting patterns
+        match self
+            .package
+            .iter()
+            .find(|spec| !members.contains(&spec.as_str()))
+        {
+            Some(spec) => Err(crate::Error::PackageNotFound {
+                spec: spec.clone(),
+                similar: crate::error::similar_name(spec, members.iter().copied())
+                    .map(ToOwned::to_owned),
+            }),
+            None => Ok(()),
+        }
+    }
+
+    /// Partition the packages of any [`WorkspaceGraph`][crate::WorkspaceGraph] like
+    /// [`Workspace::partition_packages_with`], returning their ids.
+    pub fn partition_graph<'g, G: crate::WorkspaceGraph>(
+        &self,
+        graph: &'g G,
+        options: &crate::WalkOptions,
+    ) -> (Vec<&'g G::Id>, Vec<&'g G::Id>) {
+        let (included, excluded): (Vec<_>, Vec<_>) = self
+            .selection_reasons(graph, options)
+            .into_iter()
+            .partition(|s| s.is_selected());
+        (
+            included.into_iter().map(|s| s.id).collect(),
+            excluded.into_iter().map(|s| s.id).collect(),
+        )
+    }
+
+    /// Why each package of `graph` is selected or not by [`Workspace::partition_graph`].
+    ///
+    /// See [`render_selection`][crate::render_selection] for `--explain-selection`.
+    pub fn selection_reasons<'g, G: crate::WorkspaceGraph>(
+        &self,
+        graph: &'g G,
+        options: &crate::WalkOptions,
+    ) -> Vec<crate::PackageSelection<'g, G::Id>> {
         let selection =
             Packages::from_flags(self.workspace || self.all, &self.exclude, &self.package);
-        let workspace_members: collections::HashSet<_> = meta.workspace_members.iter().collect();
-        let base_ids: collections::HashSet<_> = match selection {
+        let workspace_members: collections::HashSet<_> =
+            graph.workspace_members().into_iter().collect();
+        let mut reasons: collections::HashMap<_, _> = match selection {
             Packages::Default => {
                 // Deviating from cargo because Metadata doesn't have default members
-                let resolve = meta.resolve.as_ref().expect("no-deps is unsupported");
-                match &resolve.root {
-                    Some(root) => {
-                        let mut base_ids = collections::HashSet::new();
-                        base_ids.insert(root);
-                        base_ids
-                    }
-                    None => workspace_members,
+                match graph.root() {
+                    Some(root) => std::iter::once((root, crate::SelectionReason::Root)).collect(),
+                    None => workspace_members
+                        .iter()
+                        .map(|id| (*id, crate::SelectionReason::DefaultMembers))
+                        .collect(),
                 }
             }
-            Packages::All => workspace_members,
-            Packages::OptOut(_) => workspace_members, // Deviating from cargo by only checking workspace members
+            Packages::All | Packages::OptOut(_) => {
+                // Deviating from cargo by only checking workspace members
+                workspace_members
+                    .iter()
+                    .map(|id| (*id, crate::SelectionReason::Workspace))
+                    .collect()
+            }
             Packages::Packages(patterns) => {
-                meta.packages
+                workspace_members
                     .iter()
-                    // Deviating from cargo by not supporting patterns
                     // Deviating from cargo by only checking workspace members
-                    .filter(|p| workspace_members.contains(&p.id) && patterns.contains(&p.name))
-                    .map(|p| &p.id)
+                    .filter_map(|id| {
+                        let name = graph.package_name(id);
+                        // Deviating from cargo by not supporting patterns
+                        let spec = patterns.iter().find(|p| *p == name)?;
+                        Some((*id, crate::SelectionReason::Package(spec.clone())))
+                    })
                     .collect()
             }
         };
 
-        meta.packages
-            .iter()
-            // Deviating from cargo by not supporting patterns
-            .partition(|p| base_ids.contains(&p.id) && !self.exclude.contains(&p.name))
+        if self.with_dependents || self.with_dependencies {
+            let dependency_graph = crate::DependencyGraph::from_graph(graph);
+            let base_ids: Vec<_> = reasons.keys().copied().collect();
+            let mut expanded = Vec::new();
+            if self.with_dependents {
+                let dependents = dependency_graph.dependents(base_ids.iter().copied(), options);
+                expanded.extend(
+                    dependents
+                        .into_iter()
+                        .map(|id| (id, crate::SelectionReason::Dependent)),
+                );
+            }
+            if self.with_dependencies {
+                let dependencies = dependency_graph.dependencies(base_ids.iter().copied(), options);
+                expanded.extend(
+                    dependencies
+                        .into_iter()
+                        .map(|id| (id, crate::SelectionReason::Dependency)),
+                );
+            }
+            for (id, reason) in expanded {
+                if workspace_members.contains(id) {
+                    reasons.entry(id).or_insert(reason);
+                }
+            }
+        }
+
+        graph
+            .package_ids()
+            .into_iter()
+            .map(|id| {
+                let name = graph.package_name(id);
+                // Deviating from cargo by not supporting patterns
+                let reason = if !workspace_members.contains(id) {
+                    crate::SelectionReason::NotMember
+                } else if let Some(spec) = self.exclude.iter().find(|spec| *spec == name) {
+                    crate::SelectionReason::Excluded(spec.clone())
+                } else {
+                    reasons
+                        .remove(id)
+                        .unwrap_or(crate::SelectionReason::NotSelected)
+                };
+                crate::PackageSelection { id, name, reason }
+            })
+            .collect()
+    }
+}
+
+#[cfg(feature = "escargot")]
+impl Workspace {
+    /// Forward these flags to an `escargot::CargoBuild`.
+    ///
+    /// `--with-dependents` and `--with-dependencies` have no `cargo` equivalent and are not
+    /// forwarded; forward the result of `Workspace::partition_packages` with
+    /// `CargoBuild::package` instead.  Like `Workspace::partition_packages`, `--exclude` implies
+    /// `--workspace` and is ignored with `--package`.
+    ///
+    /// Note: Requires the features `escargot`.
+    pub fn forward_escargot(&self, mut cmd: escargot::CargoBuild) -> escargot::CargoBuild {
+        // `cargo` only accepts `--exclude` with `--workspace`, see `Packages::from_flags`
+        let all = self.workspace || self.all;
+        let opt_out = !self.exclude.is_empty() && (all || self.package.is_empty());
+        if all || opt_out {
+            cmd = cmd.arg("--workspace");
+        }
+        for package in &self.package {
+            cmd = cmd.package(package);
+        }
+        if opt_out {
+            for exclude in &self.exclude {
+                cmd = cmd.arg("--exclude").arg(exclude);
+            }
+        }
+        cmd
     }
 }
 
 // See cargo's src/cargo/ops/cargo_compile.rs
 #[derive(Clone, PartialEq, Eq, Debug)]
-#[cfg(feature = "cargo_metadata")]
 #[allow(clippy::enum_variant_names)]
 enum Packages<'p> {
     Default,
@@ -80,7 +278,6 @@ enum Packages<'p> {
     Packages(&'p [String]),
 }
 
-#[cfg(feature = "cargo_metadata")]
 impl<'p> Packages<'p> {
     pub fn from_flags(all: bool, exclude: &'p [String], package: &'p [String]) -> Self {
         match (all, exclude.len(), package.len()) {
@@ -94,6 +291,142 @@ impl<'p> Packages<'p> {
     }
 }
 
+crate::args::flag_group_args!(WorkspaceArgs for Workspace {
+    "package",
+    "workspace",
+    "all",
+    "exclude",
+    "with-dependents",
+    "with-dependencies",
+});
+
+impl WorkspaceArgs {
+    /// Register the flags on `cmd`.
+    pub fn augment_args<'help>(&'help self, cmd: clap::Command<'help>) -> clap::Command<'help> {
+        let names = &self.names;
+        cmd.arg(
+            names
+                .short(names.arg("package"), 'p')
+                .value_name("SPEC")
+                .multiple_occurrences(true)
+                .help("Package to process (see `cargo help pkgid`)"),
+        )
+        .arg(
+            names
+                .arg("workspace")
+                .help("Process all packages in the workspace"),
+        )
+        .arg(
+            names
+                .arg("all")
+                .hide(true)
+                .help("Process all packages in the workspace"),
+        )
+        .arg(
+            names
+                .arg("exclude")
+                .value_name("SPEC")
+                .multiple_occurrences(true)
+                .help("Exclude packages from being processed"),
+        )
+        .arg(
+            names
+                .arg("with-dependents")
+                .help("Also process workspace members that depend on the selected packages"),
+        )
+        .arg(
+            names
+                .arg("with-dependencies")
+                .help("Also process workspace members that the selected packages depend on"),
+        )
+    }
+
+    /// Read the flags registered by [`WorkspaceArgs::augment_args`] from `matches`.
+    pub fn from_arg_matches(&self, matches: &clap::ArgMatches) -> Workspace {
+        let names = &self.names;
+        Workspace {
+            package: names.values(matches, "package"),
+            workspace: names.flag(matches, "workspace"),
+            all: names.flag(matches, "all"),
+            exclude: names.values(matches, "exclude"),
+            with_dependents: names.flag(matches, "with-dependents"),
+            with_dependencies: names.flag(matches, "with-dependencies"),
+        }
+    }
+}
+
+impl Workspace {
+    /// Build [`Workspace`] flags programmatically.
+    ///
+    /// ```rust
+    /// let workspace = clap_cargo::Workspace::builder()
+    ///     .workspace(true)
+    ///     .exclude("xtask")
+    ///     .build();
+    /// assert_eq!(workspace.exclude, ["xtask"]);
+    /// ```
+    pub fn builder() -> WorkspaceBuilder {
+        WorkspaceBuilder::default()
+    }
+}
+
+/// Builder for [`Workspace`], see [`Workspace::builder`].
+#[derive(Default, Clone, Debug, PartialEq, Eq)]
+pub struct WorkspaceBuilder {
+    inner: Workspace,
+}
+
+impl WorkspaceBuilder {
+    /// Process `package`, in addition to earlier ones
+    pub fn package(mut self, package: impl Into<String>) -> Self {
+        self.inner.package.push(package.into());
+        self
+    }
+
+    /// Process each of `packages`, in addition to earlier ones
+    pub fn packages(mut self, packages: impl IntoIterator<Item = impl Into<String>>) -> Self {
+        self.inner
+            .package
+            .extend(packages.into_iter().map(Into::into));
+        self
+    }
+
+    /// Process all packages in the workspace
+    pub fn workspace(mut self, yes: bool) -> Self {
+        self.inner.workspace = yes;
+        self
+    }
+
+    /// Exclude a package from being processed, in addition to earlier ones
+    pub fn exclude(mut self, exclude: impl Into<String>) -> Self {
+        self.inner.exclude.push(exclude.into());
+        self
+    }
+
+    /// Also process workspace members that depend on the selected packages
+    pub fn with_dependents(mut self, yes: bool) -> Self {
+        self.inner.with_dependents = yes;
+        self
+    }
+
+    /// Also process workspace members that the selected packages depend on
+    pub fn with_dependencies(mut self, yes: bool) -> Self {
+        self.inner.with_dependencies = yes;
+        self
+    }
+
+    /// Finish building the flags
+    pub fn build(self) -> Workspace {
+        self.inner
+    }
+}
+
+impl From<WorkspaceBuilder> for Workspace {
+    fn from(builder: WorkspaceBuilder) -> Self {
+        builder.build()
+    }
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -129,6 +462,8 @@ mod test {
                     workspace: false,
                     all: false,
                     exclude: vec![],
+                    with_dependents: false,
+                    with_dependencies: false,
                 }
             },
             Args::parse_from(&["test"])
@@ -141,6 +476,8 @@ mod test {
                     workspace: false,
                     all: false,
                     exclude: vec![],
+                    with_dependents: false,
+                    with_dependencies: false,
                 }
             },
             Args::parse_from(&["test", "--package", "foo", "--package", "bar", "baz"])
@@ -153,12 +490,28 @@ mod test {
                     workspace: false,
                     all: false,
                     exclude: vec!["foo".to_owned(), "bar".to_owned()],
+                    with_dependents: false,
+                    with_dependencies: false,
                 }
             },
             Args::parse_from(&["test", "--exclude", "foo", "--exclude", "bar", "baz"])
         );
     }
 
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serde_round_trip() {
+        let workspace = Workspace {
+            workspace: true,
+            exclude: vec!["foo".to_owned()],
+            ..Default::default()
+        };
+        let raw = serde_json::to_value(&workspace).unwrap();
+        assert_eq!(raw["exclude"], serde_json::json!(["foo"]));
+        let round_trip: Workspace = serde_json::from_value(raw).unwrap();
+        assert_eq!(round_trip, workspace);
+    }
+
     #[cfg(feature = "cargo_metadata")]
     #[cfg(test)]
     mod partition_default {
@@ -396,4 +749,351 @@ mod test {
             assert_eq!(excluded.len(), 2);
         }
     }
+
+    #[cfg(feature = "cargo_metadata")]
+    #[cfg(test)]
+    mod partition_expand {
+        use super::*;
+
+        fn partition(workspace: &Workspace, options: &crate::WalkOptions) -> Vec<String> {
+            let mut metadata = cargo_metadata::MetadataCommand::new();
+            metadata.manifest_path("tests/fixtures/graph/Cargo.toml");
+            let metadata = metadata.exec().unwrap();
+            let (included, _) = workspace.partition_packages_with(&metadata, options);
+            let mut names: Vec<_> = included.iter().map(|p| p.name.clone()).collect();
+            names.sort();
+            names
+        }
+
+        #[test]
+        fn with_dependents() {
+            let workspace = Workspace {
+                package: vec!["base".to_owned()],
+                with_dependents: true,
+                ..Default::default()
+            };
+            let included = partition(&workspace, &Default::default());
+            assert_eq!(included, ["app", "base", "macros", "tool"]);
+        }
+
+        #[test]
+        fn with_dependencies() {
+            let workspace = Workspace {
+                package: vec!["app".to_owned()],
+                with_dependencies: true,
+                ..Default::default()
+            };
+            let included = partition(&workspace, &Default::default());
+            assert_eq!(included, ["app", "base", "macros", "testing"]);
+        }
+
+        #[test]
+        fn with_dependencies_exclude() {
+            let workspace = Workspace {
+                package: vec!["app".to_owned()],
+                exclude: vec!["macros".to_owned()],
+                with_dependencies: true,
+                ..Default::default()
+            };
+            let included = partition(&workspace, &Default::default());
+            assert_eq!(included, ["app", "base", "testing"]);
+        }
+
+        #[test]
+        fn with_dependents_limited() {
+            let workspace = Workspace {
+                package: vec!["base".to_owned()],
+                with_dependents: true,
+                ..Default::default()
+            };
+            let options = crate::WalkOptions {
+                depth: Some(1),
+                kinds: vec![crate::EdgeKind::Normal],
+            };
+            let included = partition(&workspace, &options);
+            assert_eq!(included, ["base", "macros"]);
+        }
+    }
+
+    #[cfg(test)]
+    mod partition_graph {
+        use super::*;
+        use crate::backend::test::{MemoryGraph, MemoryPackage};
+        use crate::EdgeKind;
+
+        fn graph() -> MemoryGraph {
+            MemoryGraph {
+                packages: vec![
+                    MemoryPackage {
+                        id: 1,
+                        name: "base",
+                        ..Default::default()
+                    },
+                    MemoryPackage {
+                        id: 2,
+                        name: "app",
+                        dependencies: vec![(1, EdgeKind::Normal), (3, EdgeKind::Normal)],
+                        ..Default::default()
+                    },
+                    MemoryPackage {
+                        id: 3,
+                        name: "external",
+                        ..Default::default()
+                    },
+                    MemoryPackage {
+                        id: 4,
+                        name: "other",
+                        ..Default::default()
+                    },
+                ],
+                members: vec![1, 2, 4],
+                root: None,
+            }
+        }
+
+        fn partition(workspace: &Workspace) -> (Vec<u32>, Vec<u32>) {
+            let graph = graph();
+            let (included, excluded) = workspace.partition_graph(&graph, &Default::default());
+            (
+                included.into_iter().copied().collect(),
+                excluded.into_iter().copied().collect(),
+            )
+        }
+
+        #[test]
+        fn default_members() {
+            assert_eq!(partition(&Workspace::default()), (vec![1, 2, 4], vec![3]));
+        }
+
+        #[test]
+        fn default_root() {
+            let graph = MemoryGraph {
+                root: Some(2),
+                ..graph()
+            };
+            let (included, _) = Workspace::default().partition_graph(&graph, &Default::default());
+            assert_eq!(included, [&2]);
+        }
+
+        #[test]
+        fn package_with_dependencies() {
+            let workspace = Workspace {
+                package: vec!["app".to_owned()],
+                with_dependencies: true,
+                ..Default::default()
+            };
+            assert_eq!(partition(&workspace), (vec![1, 2], vec![3, 4]));
+        }
+
+        #[test]
+        fn exclude() {
+            let workspace = Workspace {
+                workspace: true,
+                exclude: vec!["base".to_owned()],
+                ..Default::default()
+            };
+            assert_eq!(partition(&workspace), (vec![2, 4], vec![1, 3]));
+        }
+
+        #[test]
+        fn explain() {
+            let graph = graph();
+            let workspace = Workspace {
+                package: vec!["app".to_owned(), "other".to_owned()],
+                exclude: vec!["other".to_owned()],
+                with_dependencies: true,
+                ..Default::default()
+            };
+            let reasons: Vec<_> = workspace
+                .selection_reasons(&graph, &Default::default())
+                .into_iter()
+                .map(|s| (s.name, s.reason))
+                .collect();
+            assert_eq!(
+                reasons,
+                [
+                    ("base", crate::SelectionReason::Dependency),
+                    ("app", crate::SelectionReason::Package("app".to_owned())),
+                    ("external", crate::SelectionReason::NotMember),
+                    (
+                        "other",
+                        crate::SelectionReason::Excluded("other".to_owned())
+                    ),
+                ]
+            );
+
+            let reasons: Vec<_> = Workspace::default()
+                .selection_reasons(&graph, &Default::default())
+                .into_iter()
+                .map(|s| s.reason)
+                .collect();
+            assert_eq!(reasons[0], crate::SelectionReason::DefaultMembers);
+        }
+    }
+
+    #[cfg(feature = "cargo_metadata")]
+    #[cfg(test)]
+    mod validate {
+        use super::*;
+
+        fn metadata(no_deps: bool) -> cargo_metadata::Metadata {
+            let mut metadata = cargo_metadata::MetadataCommand::new();
+            metadata.manifest_path("tests/fixtures/mixed_ws/Cargo.toml");
+            if no_deps {
+                metadata.no_deps();
+            }
+            metadata.exec().unwrap()
+        }
+
+        #[test]
+        fn members() {
+            let workspace = Workspace {
+                package: vec!["a".to_owned(), "c".to_owned()],
+                ..Default::default()
+            };
+            workspace.validate(&metadata(false)).unwrap();
+        }
+
+        #[test]
+        fn unknown_package() {
+            let workspace = Workspace {
+                package: vec!["a".to_owned(), "bb".to_owned()],
+                ..Default::default()
+            };
+            let err = workspace.validate(&metadata(false)).unwrap_err();
+            assert_eq!(
+                err.to_string(),
+                "package ID specification `bb` did not match any packages
+help: a package with a similar name exists: `b`"
+            );
+        }
+
+        #[test]
+        fn no_deps() {
+            let err = Workspace::default().validate(&metadata(true)).unwrap_err();
+            assert!(matches!(err, crate::Error::NoDeps), "{:?}", err);
+        }
+
+        #[test]
+        fn partition_no_deps() {
+            let meta = metadata(true);
+            let workspace = Workspace {
+                package: vec!["a".to_owned()],
+                with_dependents: true,
+                with_dependencies: true,
+                ..Default::default()
+            };
+            let (included, _) = workspace.partition_packages(&meta);
+            let included: Vec<_> = included.iter().map(|p| p.name.as_str()).collect();
+            assert_eq!(included, ["a"]);
+        }
+    }
+
+    #[cfg(feature = "escargot")]
+    #[test]
+    fn forward_escargot() {
+        fn build(workspace: &Workspace) -> Result<(), escargot::error::CargoError> {
+            let cmd =
+                escargot::CargoBuild::new().manifest_path("tests/fixtures/pure_ws/Cargo.toml");
+            crate::test_support::build(workspace.forward_escargot(cmd))
+        }
+
+        build(&Workspace {
+            workspace: true,
+            exclude: vec!["b".to_owned()],
+            ..Default::default()
+        })
+        .unwrap();
+        // `--exclude` implies `--workspace`, like `partition_packages`
+        build(&Workspace {
+            exclude: vec!["b".to_owned()],
+            ..Default::default()
+        })
+        .unwrap();
+        build(&Workspace {
+            package: vec!["a".to_owned()],
+            exclude: vec!["b".to_owned()],
+            ..Default::default()
+        })
+        .unwrap();
+        build(&Workspace {
+            package: vec!["missing".to_owned()],
+            ..Default::default()
+        })
+        .unwrap_err();
+    }
+
+    #[test]
+    fn args_with_prefix() {
+        let base = WorkspaceArgs::with_prefix("base-");
+        let head = WorkspaceArgs::with_prefix("head-");
+        let cmd = head.augment_args(base.augment_args(clap::Command::new("test")));
+        cmd.clone().debug_assert();
+
+        let matches = cmd
+            .try_get_matches_from([
+                "test",
+                "--base-package",
+                "foo",
+                "--base-package",
+                "bar",
+                "--head-workspace",
+                "--head-exclude",
+                "baz",
+            ])
+            .unwrap();
+        assert_eq!(
+            base.from_arg_matches(&matches),
+            Workspace {
+                package: vec!["foo".to_owned(), "bar".to_owned()],
+                ..Default::default()
+            }
+        );
+        assert_eq!(
+            head.from_arg_matches(&matches),
+            Workspace {
+                workspace: true,
+                exclude: vec!["baz".to_owned()],
+                ..Default::default()
+            }
+        );
+    }
+
+    #[test]
+    fn args_match_derive() {
+        let args = WorkspaceArgs::new();
+        let cmd = args.augment_args(clap::Command::new("test"));
+        cmd.clone().debug_assert();
+
+        let argv = ["test", "-p", "foo", "--all", "--with-dependents"];
+        let matches = cmd.try_get_matches_from(argv).unwrap();
+        #[derive(Debug, StructOpt)]
+        struct Args {
+            #[clap(flatten)]
+            workspace: Workspace,
+        }
+        assert_eq!(
+            args.from_arg_matches(&matches),
+            Args::parse_from(argv).workspace
+        );
+    }
+
+    #[test]
+    fn builder() {
+        let workspace = Workspace::builder()
+            .package("a")
+            .packages(vec!["b", "c"])
+            .exclude("d")
+            .with_dependents(true)
+            .build();
+        assert_eq!(
+            workspace,
+            Workspace {
+                package: vec!["a".to_owned(), "b".to_owned(), "c".to_owned()],
+                exclude: vec!["d".to_owned()],
+                with_dependents: true,
+                ..Default::default()
+            }
+        );
+    }
 }